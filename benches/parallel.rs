@@ -0,0 +1,27 @@
+use std::hint::black_box;
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+
+use common_log_parser::parallel::parse_lines_parallel;
+use common_log_parser::parse_common_log;
+
+// The 1M-line sample the parallel parser was introduced with.
+const LINES: usize = 1_000_000;
+
+fn bench_parallel(c: &mut Criterion) {
+    let lines = (0..LINES)
+        .map(|i| format!("10.0.{}.{} - - [15/Jan/2024:10:24:12 +0000] \"GET /item/{i} HTTP/1.1\" 200 {i}", i % 256, i / 256 % 256))
+        .collect::<Vec<_>>();
+
+    let mut group = c.benchmark_group("parallel");
+    group.throughput(Throughput::Elements(LINES as u64));
+    group.sample_size(10);
+    group.bench_function("sequential", |b| {
+        b.iter(|| black_box(lines.iter().map(|line| parse_common_log(line)).collect::<Vec<_>>()))
+    });
+    group.bench_function("rayon", |b| b.iter(|| black_box(parse_lines_parallel(&lines))));
+    group.finish();
+}
+
+criterion_group!(benches, bench_parallel);
+criterion_main!(benches);
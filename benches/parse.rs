@@ -0,0 +1,75 @@
+// Run with `cargo bench --bench parse -- --save-baseline main`, then compare a change against
+// it with `--baseline main`. Throughput is reported per parsed line.
+use std::hint::black_box;
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+
+use common_log_parser::{FormatSpec, LogFormat, parse_combined_log, parse_common_log, parse_with_format};
+
+const COMMON_LINES: [&str; 4] = [
+    "127.0.0.1 - - [01/Jan/2024:12:00:00 +0000] \"GET /api HTTP/1.1\" 200 1234",
+    "10.0.0.5 - frank [15/Jan/2024:10:24:12 -0700] \"POST /api/login?next=%2Fhome HTTP/1.1\" 201 567",
+    "2001:db8::1 - - [15/Jan/2024:10:27:15 +0000] \"GET /static/app.js HTTP/2.0\" 304 -",
+    "203.0.113.42 - - [15/Jan/2024:10:29:47 +0100] \"DELETE /users/42 HTTP/1.1\" 403 89",
+];
+
+const COMBINED_LINES: [&str; 3] = [
+    "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"GET /index.html HTTP/1.1\" 200 5120 \"https://example.com/\" \"Mozilla/5.0 (X11; Linux x86_64) Firefox/120.0\"",
+    "8.8.8.8 - - [15/Jan/2024:10:24:13 +0000] \"GET /robots.txt HTTP/1.1\" 404 0 \"-\" \"Googlebot/2.1 (+http://www.google.com/bot.html)\"",
+    "203.0.113.42 - - [15/Jan/2024:10:24:14 +0000] \"POST /api/upload HTTP/1.1\" 201 42 \"-\" \"curl/8.0\"",
+];
+
+const COMBINED_TEMPLATE: &str = r#"%h %l %u %t \"%r\" %>s %b \"%{Referer}i\" \"%{User-agent}i\""#;
+
+fn bench_builtin_formats(c: &mut Criterion) {
+    let mut group = c.benchmark_group("builtin");
+
+    group.throughput(Throughput::Elements(COMMON_LINES.len() as u64));
+    group.bench_function("common", |b| {
+        b.iter(|| {
+            for line in COMMON_LINES {
+                black_box(parse_common_log(black_box(line)).unwrap());
+            }
+        })
+    });
+
+    group.throughput(Throughput::Elements(COMBINED_LINES.len() as u64));
+    group.bench_function("combined", |b| {
+        b.iter(|| {
+            for line in COMBINED_LINES {
+                black_box(parse_combined_log(black_box(line)).unwrap());
+            }
+        })
+    });
+
+    group.finish();
+}
+
+// `parse_with_format` compiles a custom template on every call; `FormatSpec` compiles it once.
+fn bench_custom_template(c: &mut Criterion) {
+    let mut group = c.benchmark_group("custom");
+    group.throughput(Throughput::Elements(COMBINED_LINES.len() as u64));
+
+    let format = LogFormat::Custom(COMBINED_TEMPLATE.to_string());
+    group.bench_function("recompiled", |b| {
+        b.iter(|| {
+            for line in COMBINED_LINES {
+                black_box(parse_with_format(black_box(line), &format).unwrap());
+            }
+        })
+    });
+
+    let spec = FormatSpec::from_apache_directive(COMBINED_TEMPLATE).unwrap();
+    group.bench_function("precompiled", |b| {
+        b.iter(|| {
+            for line in COMBINED_LINES {
+                black_box(spec.parse(black_box(line)).unwrap());
+            }
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_builtin_formats, bench_custom_template);
+criterion_main!(benches);
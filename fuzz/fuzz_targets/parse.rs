@@ -0,0 +1,17 @@
+// Run with `cargo +nightly fuzz run parse` from the repository root. Any input must come back
+// as `Ok` or `Err`: the only `unwrap`s are on the static patterns, so a panic here points at
+// the timestamp, number or request parsing paths.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use common_log_parser::{parse_combined_log, parse_common_log};
+
+fuzz_target!(|line: &str| {
+    for result in [parse_common_log(line), parse_combined_log(line)] {
+        // Formatting a parsed entry must not panic either.
+        if let Ok(entry) = result {
+            let _ = entry.to_string();
+        }
+    }
+});
@@ -0,0 +1,77 @@
+use std::iter::Peekable;
+
+use crate::parser::LogEntry;
+
+/// Collapses runs of consecutive entries with the same ip, method, path and status,
+/// yielding the first entry of each run with the run length, like `uniq -c`.
+pub struct DedupConsecutive<I: Iterator<Item = LogEntry>> {
+    entries: Peekable<I>,
+}
+
+impl<I: Iterator<Item = LogEntry>> DedupConsecutive<I> {
+    pub fn new(entries: impl IntoIterator<IntoIter = I>) -> Self {
+        DedupConsecutive {
+            entries: entries.into_iter().peekable(),
+        }
+    }
+}
+
+fn is_repeat(a: &LogEntry, b: &LogEntry) -> bool {
+    a.ip == b.ip && a.method == b.method && a.path == b.path && a.status == b.status
+}
+
+impl<I: Iterator<Item = LogEntry>> Iterator for DedupConsecutive<I> {
+    type Item = (LogEntry, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let first = self.entries.next()?;
+        let mut count = 1;
+        while self.entries.next_if(|entry| is_repeat(&first, entry)).is_some() {
+            count += 1;
+        }
+        Some((first, count))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    fn entry(ip: u8, path: &str, status: u16, size: u64) -> LogEntry {
+        LogEntry::builder()
+            .ip(Ipv4Addr::new(10, 0, 0, ip))
+            .method("GET")
+            .path(path)
+            .status(status)
+            .size(size)
+            .build()
+    }
+
+    #[test]
+    fn test_collapses_consecutive_runs() {
+        let entries = vec![
+            entry(1, "/api", 503, 10),
+            entry(1, "/api", 503, 20),
+            entry(1, "/api", 503, 30),
+            entry(2, "/api", 503, 10),
+            entry(1, "/api", 200, 10),
+            entry(1, "/api", 503, 10),
+        ];
+
+        let runs = DedupConsecutive::new(entries)
+            .map(|(entry, count)| (entry.ip.to_string(), entry.status, entry.size, count))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            runs,
+            [
+                ("10.0.0.1".to_string(), 503, 10, 3),
+                ("10.0.0.2".to_string(), 503, 10, 1),
+                ("10.0.0.1".to_string(), 200, 10, 1),
+                ("10.0.0.1".to_string(), 503, 10, 1),
+            ]
+        );
+
+        assert_eq!(DedupConsecutive::new(Vec::new()).count(), 0);
+    }
+}
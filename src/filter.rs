@@ -0,0 +1,283 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use thiserror::Error;
+
+use crate::parser::LogEntry;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum FilterError {
+    #[error("invalid status filter {0:?}, expected a code like 404 or a class like 4xx")]
+    InvalidStatus(String),
+
+    #[error("invalid timestamp {0:?}, expected RFC3339 or 01/Jan/2024:12:00:00 +0000")]
+    InvalidTimestamp(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StatusMatcher {
+    /// A single status code, e.g. `404`.
+    Exact(u16),
+    /// A status class given by its first digit, e.g. `5xx`.
+    Class(u16),
+}
+
+impl StatusMatcher {
+    pub fn matches(&self, status: u16) -> bool {
+        match *self {
+            StatusMatcher::Exact(code) => status == code,
+            StatusMatcher::Class(class) => status / 100 == class,
+        }
+    }
+}
+
+impl FromStr for StatusMatcher {
+    type Err = FilterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || FilterError::InvalidStatus(s.to_string());
+
+        let bytes = s.as_bytes();
+        if bytes.len() != 3 || !bytes[0].is_ascii_digit() {
+            return Err(invalid());
+        }
+
+        let rest = &s[1..];
+        if rest.eq_ignore_ascii_case("xx") && bytes[0] != b'0' {
+            return Ok(StatusMatcher::Class(u16::from(bytes[0] - b'0')));
+        }
+        if !rest.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        let code = s.parse::<u16>().map_err(|_| invalid())?;
+        Ok(StatusMatcher::Exact(code))
+    }
+}
+
+/// Keeps statuses matching any `include` matcher, or every status when there are none,
+/// then drops those matching any `exclude` matcher. Exclusion wins, so `4xx` included and
+/// `404` excluded keeps every client error but 404.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StatusFilter {
+    pub include: Vec<StatusMatcher>,
+    pub exclude: Vec<StatusMatcher>,
+}
+
+impl StatusFilter {
+    pub fn new(include: Vec<StatusMatcher>, exclude: Vec<StatusMatcher>) -> Self {
+        StatusFilter { include, exclude }
+    }
+
+    pub fn matches(&self, status: u16) -> bool {
+        let included = self.include.is_empty() || self.include.iter().any(|matcher| matcher.matches(status));
+        included && !self.exclude.iter().any(|matcher| matcher.matches(status))
+    }
+}
+
+/// Both bounds are inclusive; a missing bound leaves that side open.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TimeRange {
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl TimeRange {
+    pub fn new(since: Option<DateTime<Utc>>, until: Option<DateTime<Utc>>) -> Self {
+        TimeRange { since, until }
+    }
+
+    pub fn contains(&self, timestamp: &DateTime<Utc>) -> bool {
+        self.since.is_none_or(|since| *timestamp >= since) && self.until.is_none_or(|until| *timestamp <= until)
+    }
+}
+
+/// Keeps entries whose path contains `contains` and matches `regex`; an unset pattern
+/// matches every path. `invert` keeps the entries that would otherwise be dropped.
+#[derive(Debug, Clone, Default)]
+pub struct PathFilter {
+    pub contains: Option<String>,
+    pub regex: Option<Regex>,
+    pub invert: bool,
+}
+
+impl PathFilter {
+    pub fn new(contains: Option<String>, regex: Option<Regex>, invert: bool) -> Self {
+        PathFilter { contains, regex, invert }
+    }
+
+    pub fn matches(&self, entry: &LogEntry) -> bool {
+        let contains = self.contains.as_deref().is_none_or(|needle| entry.path.contains(needle));
+        let regex = self.regex.as_ref().is_none_or(|regex| regex.is_match(&entry.path));
+        (contains && regex) != self.invert
+    }
+}
+
+/// Parses an RFC3339 timestamp or one in the common log `%d/%b/%Y:%H:%M:%S %z` layout.
+pub fn parse_timestamp(s: &str) -> Result<DateTime<Utc>, FilterError> {
+    DateTime::parse_from_rfc3339(s)
+        .or_else(|_| DateTime::parse_from_str(s, "%d/%b/%Y:%H:%M:%S %z"))
+        .map(|timestamp| timestamp.to_utc())
+        .map_err(|_| FilterError::InvalidTimestamp(s.to_string()))
+}
+
+// Lowercase substrings of known crawler and scripted-client user agents.
+const BOT_SIGNATURES: &[&str] = &[
+    "googlebot",
+    "bingbot",
+    "slurp",
+    "duckduckbot",
+    "baiduspider",
+    "yandexbot",
+    "applebot",
+    "facebookexternalhit",
+    "ahrefsbot",
+    "semrushbot",
+    "mj12bot",
+    "petalbot",
+    "gptbot",
+    "bot/",
+    "crawler",
+    "spider",
+    "curl/",
+    "wget/",
+    "python-requests",
+    "python-urllib",
+    "go-http-client",
+    "java/",
+    "okhttp",
+    "libwww-perl",
+    "httpclient",
+];
+
+pub fn is_bot(user_agent: &str) -> bool {
+    let user_agent = user_agent.to_ascii_lowercase();
+    BOT_SIGNATURES.iter().any(|signature| user_agent.contains(signature))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_status_matcher() {
+        let exact = "404".parse::<StatusMatcher>().unwrap();
+        assert_eq!(exact, StatusMatcher::Exact(404));
+        assert!(exact.matches(404));
+        assert!(!exact.matches(400));
+
+        let class = "5xx".parse::<StatusMatcher>().unwrap();
+        assert_eq!(class, StatusMatcher::Class(5));
+        assert!(class.matches(500));
+        assert!(class.matches(599));
+        assert!(!class.matches(499));
+        assert_eq!("4XX".parse::<StatusMatcher>().unwrap(), StatusMatcher::Class(4));
+    }
+
+    #[test]
+    fn test_invalid_status_matcher() {
+        for input in ["", "xx", "0xx", "55xx", "5x", "40", "4040", "abc", "+40"] {
+            let matcher = input.parse::<StatusMatcher>();
+            assert_eq!(matcher, Err(FilterError::InvalidStatus(input.to_string())), "Wrong result for: {input}");
+        }
+    }
+
+    #[test]
+    fn test_time_range_is_inclusive() {
+        let since = Utc.with_ymd_and_hms(2024, 1, 15, 10, 0, 0).unwrap();
+        let until = Utc.with_ymd_and_hms(2024, 1, 15, 11, 0, 0).unwrap();
+        let range = TimeRange::new(Some(since), Some(until));
+
+        assert!(range.contains(&since));
+        assert!(range.contains(&until));
+        assert!(range.contains(&Utc.with_ymd_and_hms(2024, 1, 15, 10, 30, 0).unwrap()));
+        assert!(!range.contains(&Utc.with_ymd_and_hms(2024, 1, 15, 9, 59, 59).unwrap()));
+        assert!(!range.contains(&Utc.with_ymd_and_hms(2024, 1, 15, 11, 0, 1).unwrap()));
+
+        assert!(TimeRange::new(Some(since), None).contains(&Utc.with_ymd_and_hms(2030, 1, 1, 0, 0, 0).unwrap()));
+        assert!(TimeRange::new(None, Some(until)).contains(&Utc.with_ymd_and_hms(2000, 1, 1, 0, 0, 0).unwrap()));
+        assert!(TimeRange::default().contains(&since));
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        let expected = Utc.with_ymd_and_hms(2024, 1, 15, 10, 24, 12).unwrap();
+
+        assert_eq!(parse_timestamp("2024-01-15T10:24:12Z"), Ok(expected));
+        assert_eq!(parse_timestamp("2024-01-15T12:24:12+02:00"), Ok(expected));
+        assert_eq!(parse_timestamp("15/Jan/2024:10:24:12 +0000"), Ok(expected));
+        assert_eq!(
+            parse_timestamp("yesterday"),
+            Err(FilterError::InvalidTimestamp("yesterday".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_is_bot() {
+        let cases = [
+            ("Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)", true),
+            ("Mozilla/5.0 (compatible; bingbot/2.0; +http://www.bing.com/bingbot.htm)", true),
+            ("Mozilla/5.0 (compatible; YandexBot/3.0; +http://yandex.com/bots)", true),
+            ("curl/8.4.0", true),
+            ("Wget/1.21.4", true),
+            ("python-requests/2.31.0", true),
+            ("Go-http-client/1.1", true),
+            ("Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0 Safari/537.36", false),
+            ("Mozilla/5.0 (iPhone; CPU iPhone OS 17_2 like Mac OS X) AppleWebKit/605.1.15 Mobile/15E148", false),
+            ("Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:121.0) Gecko/20100101 Firefox/121.0", false),
+            ("", false),
+        ];
+
+        for (user_agent, expected) in cases.iter() {
+            assert_eq!(is_bot(user_agent), *expected, "Wrong result for: {user_agent}");
+        }
+    }
+
+    #[test]
+    fn test_path_filter() {
+        let paths = ["/api/users", "/api/login", "/static/app.js", "/health"];
+        let kept = |filter: &PathFilter| {
+            paths
+                .iter()
+                .filter(|path| filter.matches(&LogEntry::builder().path(**path).build()))
+                .copied()
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(kept(&PathFilter::default()), paths);
+        assert_eq!(
+            kept(&PathFilter::new(Some("/api".to_string()), None, false)),
+            ["/api/users", "/api/login"]
+        );
+        assert_eq!(
+            kept(&PathFilter::new(None, Some(Regex::new(r"\.js$").unwrap()), true)),
+            ["/api/users", "/api/login", "/health"]
+        );
+
+        let both = PathFilter::new(Some("api".to_string()), Some(Regex::new("^/api/l").unwrap()), false);
+        assert_eq!(kept(&both), ["/api/login"]);
+        let inverted = PathFilter { invert: true, ..both };
+        assert_eq!(kept(&inverted), ["/api/users", "/static/app.js", "/health"]);
+    }
+
+    #[test]
+    fn test_status_filter() {
+        let statuses = [200, 204, 301, 404, 429, 500];
+        let kept = |include: &[&str], exclude: &[&str]| {
+            let matchers = |specs: &[&str]| specs.iter().map(|spec| spec.parse().unwrap()).collect();
+            let filter = StatusFilter::new(matchers(include), matchers(exclude));
+            statuses
+                .into_iter()
+                .filter(|status| filter.matches(*status))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(kept(&[], &[]), statuses);
+        assert_eq!(kept(&[], &["2xx"]), [301, 404, 429, 500]);
+        assert_eq!(kept(&[], &["2xx", "301"]), [404, 429, 500]);
+        // Include first, then exclude: the exclusion wins where both match.
+        assert_eq!(kept(&["4xx", "5xx"], &["404"]), [429, 500]);
+        assert!(kept(&["404"], &["4xx"]).is_empty());
+    }
+}
@@ -0,0 +1,118 @@
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+/// Reads a file like `tail -f`: at end of file it polls for appended data instead of
+/// returning EOF, and reopens the path when the file is rotated or truncated.
+///
+/// Reads only return `Ok(0)` once the stop flag is set.
+pub struct FollowReader {
+    path: PathBuf,
+    file: File,
+    position: u64,
+    poll_interval: Duration,
+    stop: Arc<AtomicBool>,
+}
+
+impl FollowReader {
+    pub fn open(path: impl AsRef<Path>, poll_interval: Duration) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = File::open(&path)?;
+        Ok(FollowReader {
+            path,
+            file,
+            position: 0,
+            poll_interval,
+            stop: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    /// Setting the returned flag makes the reader report EOF at its next poll.
+    pub fn stop_handle(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.stop)
+    }
+
+    // A missing path is treated as a rotation still in progress.
+    fn rotated(&self) -> io::Result<bool> {
+        let Ok(current) = fs::metadata(&self.path) else {
+            return Ok(false);
+        };
+        if current.len() < self.position {
+            return Ok(true);
+        }
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            if current.ino() != self.file.metadata()?.ino() {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+
+impl Read for FollowReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let n = self.file.read(buf)?;
+            if n > 0 {
+                self.position += n as u64;
+                return Ok(n);
+            }
+            if self.stop.load(Ordering::Relaxed) {
+                return Ok(0);
+            }
+            if self.rotated()? {
+                self.file = File::open(&self.path)?;
+                self.position = 0;
+                continue;
+            }
+            thread::sleep(self.poll_interval);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_follows_appends_and_rotation() {
+        let path = std::env::temp_dir().join(format!("follow-{}.log", std::process::id()));
+        fs::write(&path, "first\n").unwrap();
+
+        let follower = FollowReader::open(&path, Duration::from_millis(5)).unwrap();
+        let stop = follower.stop_handle();
+        let (sender, received) = mpsc::channel();
+        let reader = thread::spawn(move || {
+            for line in BufReader::new(follower).lines() {
+                sender.send(line.unwrap()).unwrap();
+            }
+        });
+        // Each step waits for the follower to catch up, so rotation can't overtake an append.
+        let next_line = || received.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(next_line(), "first");
+
+        let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"second\n").unwrap();
+        assert_eq!(next_line(), "second");
+
+        let rotated = path.with_extension("log.1");
+        fs::rename(&path, &rotated).unwrap();
+        fs::write(&path, "third\n").unwrap();
+        assert_eq!(next_line(), "third");
+
+        stop.store(true, Ordering::Relaxed);
+        reader.join().unwrap();
+        assert!(received.try_recv().is_err());
+        fs::remove_file(rotated).unwrap();
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,496 @@
+use regex::Regex;
+use thiserror::Error;
+
+use crate::parser::{
+    LogEntry, ParseError, ParseOptions, entry_from_captures, optional_field, parse_combined_log, parse_common_log,
+    parse_vhost_combined_log, unescape_quoted,
+};
+use crate::squid::parse_squid;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogFormat {
+    Common,
+    Combined,
+    /// Combined with a leading server-name column, as Apache's `%v %h %l %u %t ...`.
+    VhostCombined,
+    /// An Apache `LogFormat` template such as `%h %l %u %t "%r" %>s %b`.
+    Custom(String),
+    /// W3C extended format (IIS); columns come from the `#Fields:` header, so it is only
+    /// usable through `LogParser` or `W3cParser`.
+    W3c,
+    /// Squid's native `access.log` format; see `parse_squid`.
+    Squid,
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum FormatError {
+    #[error("unknown log format directive {0}")]
+    UnknownDirective(String),
+
+    #[error("unterminated {0} in log format directive")]
+    UnterminatedBrace(String),
+
+    #[error("log format directive {0} appears more than once")]
+    DuplicateDirective(String),
+
+    /// The directives are valid but their combined pattern doesn't compile, e.g. because it is
+    /// too big for the regex engine.
+    #[error("log format doesn't compile to a usable pattern")]
+    InvalidPattern(#[from] regex::Error),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Directive {
+    ServerName,
+    RemoteHost,
+    RemoteLogname,
+    RemoteUser,
+    Time,
+    Request,
+    Status,
+    Size,
+    RequestHeader(String),
+    ResponseHeader(String),
+    Cookie(String),
+    /// An environment variable such as `SSL_PROTOCOL`, logged by `%{Name}x`.
+    Variable(String),
+    DurationMicros,
+    DurationSeconds,
+}
+
+impl Directive {
+    fn pattern(&self) -> &'static str {
+        match self {
+            Directive::ServerName | Directive::RemoteHost | Directive::RemoteLogname | Directive::RemoteUser => {
+                r"\S+"
+            }
+            Directive::Time => r"[^\]]+",
+            Directive::Request => r#"(?:[^"\t\\]|\\.)*"#,
+            Directive::Status => r"\S+",
+            Directive::Size => r"\S+",
+            Directive::RequestHeader(_) | Directive::ResponseHeader(_) => r#"(?:[^"\\]|\\.)*"#,
+            Directive::Cookie(_) => r#"[^"\s;]*"#,
+            Directive::Variable(_) => r#"[^"\s]*"#,
+            Directive::DurationMicros | Directive::DurationSeconds => r"[[:digit:]]+",
+        }
+    }
+
+    // Capture group read by `entry_from_captures`; other directives are
+    // captured into `LogEntry::fields` when they have a `field_name`.
+    fn group_name(&self) -> Option<&'static str> {
+        match self {
+            Directive::ServerName => Some("vhost"),
+            Directive::RemoteHost => Some("ip"),
+            Directive::RemoteLogname => Some("identity"),
+            Directive::RemoteUser => Some("user"),
+            Directive::Time => Some("timestamp"),
+            Directive::Request => Some("request"),
+            Directive::Status => Some("status"),
+            Directive::Size => Some("size"),
+            Directive::RequestHeader(name) if name.eq_ignore_ascii_case("Referer") => Some("referrer"),
+            Directive::RequestHeader(name) if name.eq_ignore_ascii_case("User-agent") => Some("user_agent"),
+            Directive::DurationMicros => Some("duration_us"),
+            Directive::DurationSeconds => Some("duration_s"),
+            Directive::RequestHeader(_)
+            | Directive::ResponseHeader(_)
+            | Directive::Cookie(_)
+            | Directive::Variable(_) => None,
+        }
+    }
+
+    fn field_name(&self) -> Option<&str> {
+        match self {
+            Directive::RequestHeader(name)
+            | Directive::ResponseHeader(name)
+            | Directive::Cookie(name)
+            | Directive::Variable(name) => Some(name),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Literal(String),
+    Field(Directive),
+}
+
+#[derive(Debug, Clone)]
+pub struct FormatSpec {
+    regex: Regex,
+    // (capture group, `LogEntry::fields` key); header names aren't valid group names.
+    fields: Vec<(String, String)>,
+}
+
+impl FormatSpec {
+    /// Compiles an Apache `LogFormat` directive, e.g. `%h %l %u %t \"%r\" %>s %b`.
+    pub fn from_apache_directive(directive: &str) -> Result<FormatSpec, FormatError> {
+        let tokens = tokenize(directive)?;
+
+        let mut pattern = String::from("^");
+        let mut groups = Vec::new();
+        let mut fields: Vec<(String, String)> = Vec::new();
+        for token in &tokens {
+            match token {
+                Token::Literal(literal) => pattern.push_str(&regex::escape(literal)),
+                Token::Field(field) => {
+                    let group = match field.group_name() {
+                        Some(name) if groups.contains(&name) => {
+                            return Err(FormatError::DuplicateDirective(name.to_string()));
+                        }
+                        Some(name) => {
+                            groups.push(name);
+                            format!("(?<{}>{})", name, field.pattern())
+                        }
+                        None => match field.field_name() {
+                            Some(name) if fields.iter().any(|(_, key)| key == name) => {
+                                return Err(FormatError::DuplicateDirective(name.to_string()));
+                            }
+                            Some(name) => {
+                                let group = format!("field{}", fields.len());
+                                let pattern = format!("(?<{}>{})", group, field.pattern());
+                                fields.push((group, name.to_string()));
+                                pattern
+                            }
+                            None => format!("(?:{})", field.pattern()),
+                        },
+                    };
+                    // `%t` renders its own brackets around the timestamp.
+                    match field {
+                        Directive::Time => pattern.push_str(&format!(r"\[{}\]", group)),
+                        _ => pattern.push_str(&group),
+                    }
+                }
+            }
+        }
+        pattern.push('$');
+
+        let regex = Regex::new(&pattern)?;
+        Ok(FormatSpec { regex, fields })
+    }
+
+    pub fn parse(&self, line: &str) -> Result<LogEntry, ParseError> {
+        self.parse_with(line, &ParseOptions::default())
+    }
+
+    /// Only line endings are trimmed, since a template may end in whitespace-separated fields.
+    pub fn parse_with(&self, line: &str, options: &ParseOptions) -> Result<LogEntry, ParseError> {
+        let line = line.trim_end_matches(['\r', '\n']);
+        let caps = self.regex.captures(line).ok_or(ParseError::InvalidFormat)?;
+
+        let mut entry = entry_from_captures(&caps, options)?;
+        for (group, key) in &self.fields {
+            if let Some(value) = caps.name(group).and_then(|m| optional_field(&unescape_quoted(m.as_str()))) {
+                entry.fields.insert(key.clone(), value);
+            }
+        }
+        Ok(entry)
+    }
+}
+
+fn tokenize(directive: &str) -> Result<Vec<Token>, FormatError> {
+    let mut tokens = Vec::new();
+    let mut literal = String::new();
+    let mut chars = directive.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '%' => {
+                let mut raw = String::from("%");
+                while let Some(c) = chars.next_if(|c| *c == '>' || *c == '<') {
+                    raw.push(c);
+                }
+                let mut argument = None;
+                if chars.next_if_eq(&'{').is_some() {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(FormatError::UnterminatedBrace(format!("{raw}{{{name}"))),
+                        }
+                    }
+                    raw.push_str(&format!("{{{name}}}"));
+                    argument = Some(name);
+                }
+                let Some(kind) = chars.next() else {
+                    return Err(FormatError::UnknownDirective(raw));
+                };
+                raw.push(kind);
+
+                let field = match (kind, argument) {
+                    ('%', None) => {
+                        literal.push('%');
+                        continue;
+                    }
+                    ('v' | 'V', None) => Directive::ServerName,
+                    ('h' | 'a', None) => Directive::RemoteHost,
+                    ('l', None) => Directive::RemoteLogname,
+                    ('u', None) => Directive::RemoteUser,
+                    ('t', None) => Directive::Time,
+                    ('r', None) => Directive::Request,
+                    ('s', None) => Directive::Status,
+                    ('b' | 'B', None) => Directive::Size,
+                    ('i', Some(name)) => Directive::RequestHeader(name),
+                    ('o', Some(name)) => Directive::ResponseHeader(name),
+                    ('C', Some(name)) => Directive::Cookie(name),
+                    ('x', Some(name)) => Directive::Variable(name),
+                    ('D', None) => Directive::DurationMicros,
+                    ('T', None) => Directive::DurationSeconds,
+                    _ => return Err(FormatError::UnknownDirective(raw)),
+                };
+                if !literal.is_empty() {
+                    tokens.push(Token::Literal(core::mem::take(&mut literal)));
+                }
+                tokens.push(Token::Field(field));
+            }
+            // Apache config strings escape quotes and tabs.
+            '\\' => match chars.next() {
+                Some('t') => literal.push('\t'),
+                Some(c) => literal.push(c),
+                None => literal.push('\\'),
+            },
+            c => literal.push(c),
+        }
+    }
+    if !literal.is_empty() {
+        tokens.push(Token::Literal(literal));
+    }
+
+    Ok(tokens)
+}
+
+/// Custom templates are compiled on every call; use `FormatSpec` to parse many lines.
+/// A lone W3C line has no `#Fields:` header to map its columns, so it fails with
+/// `ParseError::InvalidFormat`.
+pub fn parse_with_format(line: &str, format: &LogFormat) -> Result<LogEntry, ParseError> {
+    match format {
+        LogFormat::Common => parse_common_log(line),
+        LogFormat::Combined => parse_combined_log(line),
+        LogFormat::VhostCombined => parse_vhost_combined_log(line),
+        LogFormat::Custom(template) => FormatSpec::from_apache_directive(template)?.parse(line),
+        LogFormat::W3c => Err(ParseError::InvalidFormat),
+        LogFormat::Squid => parse_squid(line),
+    }
+}
+
+/// Guesses the format from a sample of lines, e.g. the start of a file. W3C wins if a
+/// `#Fields:` or `#Software:` header is present; otherwise the format that parses the most
+/// non-empty lines is chosen, preferring `Combined` on ties since every combined line is
+/// also a valid common line. Lines whose second rather than first column is an address
+/// count towards `VhostCombined`, and lines that only parse as Squid's native format
+/// towards `Squid`. Falls back to `Common` when nothing parses.
+pub fn detect_format(sample_lines: &[&str]) -> LogFormat {
+    let lines = sample_lines.iter().map(|line| line.trim()).filter(|line| !line.is_empty());
+
+    let mut vhost = 0;
+    let mut squid = 0;
+    let mut combined = 0;
+    let mut common = 0;
+    for line in lines {
+        if line.starts_with("#Fields:") || line.starts_with("#Software:") {
+            return LogFormat::W3c;
+        }
+        if parse_combined_log(line).is_ok() {
+            combined += 1;
+        } else if parse_common_log(line).is_ok() {
+            common += 1;
+        } else if parse_vhost_combined_log(line).is_ok() {
+            vhost += 1;
+        } else if parse_squid(line).is_ok() {
+            squid += 1;
+        }
+    }
+
+    if squid > vhost.max(combined).max(common) {
+        LogFormat::Squid
+    } else if vhost > combined.max(common) {
+        LogFormat::VhostCombined
+    } else if combined > 0 && combined >= common {
+        LogFormat::Combined
+    } else {
+        LogFormat::Common
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_formats() {
+        let line = "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"POST /api/login HTTP/1.1\" 201 567 \"-\" \"curl/8.0\"";
+
+        let log = parse_with_format(line, &LogFormat::Combined).unwrap();
+        assert_eq!(log.user_agent, Some("curl/8.0".to_string()));
+
+        let template = r#"%h %l %u %t \"%r\" %>s %b \"%{Referer}i\" \"%{User-agent}i\""#;
+        let custom = parse_with_format(line, &LogFormat::Custom(template.to_string())).unwrap();
+        assert_eq!(custom.ip, log.ip);
+        assert_eq!(custom.timestamp, log.timestamp);
+        assert_eq!(custom.path, log.path);
+        assert_eq!(custom.status, log.status);
+        assert_eq!(custom.size, log.size);
+        assert_eq!(custom.user_agent, log.user_agent);
+    }
+
+    #[test]
+    fn test_server_name_directive() {
+        let line = "www.example.com 10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"GET /api HTTP/1.1\" 200 567";
+
+        let format = LogFormat::Custom(r#"%v %h %l %u %t \"%r\" %>s %b"#.to_string());
+        let log = parse_with_format(line, &format).unwrap();
+        assert_eq!(log.vhost, Some("www.example.com".to_string()));
+        assert_eq!(log.ip.to_string(), "10.0.0.5");
+
+        let combined = format!("{line} \"-\" \"curl/8.0\"");
+        let log = parse_with_format(&combined, &LogFormat::VhostCombined).unwrap();
+        assert_eq!(log.vhost, Some("www.example.com".to_string()));
+        assert_eq!(log.user_agent, Some("curl/8.0".to_string()));
+    }
+
+    #[test]
+    fn test_tab_separated_format() {
+        let format = LogFormat::Custom(r"%h\t%t\t%r\t%>s\t%b".to_string());
+        let line = "10.0.0.5\t[15/Jan/2024:10:24:12 +0000]\tGET /api/users?id=5 HTTP/1.1\t200\t1234";
+
+        let log = parse_with_format(line, &format).unwrap();
+        assert_eq!(log.ip.to_string(), "10.0.0.5");
+        assert_eq!(log.path, "/api/users");
+        assert_eq!(log.query, Some("id=5".to_string()));
+        assert_eq!(log.status, 200);
+        assert_eq!(log.size, 1234);
+        assert_eq!(log.identity, None);
+
+        let crlf = parse_with_format(&format!("{line}\r\n"), &format).unwrap();
+        assert_eq!(crlf.size, 1234);
+
+        let log = parse_with_format("10.0.0.5 [15/Jan/2024:10:24:12 +0000]", &format);
+        assert!(matches!(log, Err(ParseError::InvalidFormat)));
+    }
+
+    #[test]
+    fn test_apache_directive_tokens() {
+        let directive = r#"%h %l %u %t \"%r\" %>s %b %D %T \"%{X-Forwarded-For}i\""#;
+        let tokens = tokenize(directive).unwrap();
+        assert_eq!(tokens.len(), 20);
+        assert_eq!(tokens[0], Token::Field(Directive::RemoteHost));
+        assert_eq!(tokens[1], Token::Literal(" ".to_string()));
+        assert_eq!(tokens[7], Token::Literal(" \"".to_string()));
+        assert_eq!(tokens[18], Token::Field(Directive::RequestHeader("X-Forwarded-For".to_string())));
+        assert_eq!(tokens[19], Token::Literal("\"".to_string()));
+
+        let spec = FormatSpec::from_apache_directive(directive).unwrap();
+        let line = "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"GET /api HTTP/1.1\" 200 1234 5012 0 \"192.168.0.1\"";
+        let log = spec.parse(line).unwrap();
+        assert_eq!(log.path, "/api");
+        assert_eq!(log.size, 1234);
+        assert_eq!(log.duration, Some(std::time::Duration::from_micros(5012)));
+
+        let spec = FormatSpec::from_apache_directive(r#"%h %t \"%r\" %>s %b %T"#).unwrap();
+        let line = "10.0.0.5 [15/Jan/2024:10:24:12 +0000] \"GET /api HTTP/1.1\" 200 1234 3";
+        assert_eq!(spec.parse(line).unwrap().duration, Some(std::time::Duration::from_secs(3)));
+        assert_eq!(parse_common_log(line).map(|log| log.duration).ok(), None);
+
+        let line = "10.0.0.5 [15/Jan/2024:10:24:12 +0000] \"GET /api HTTP/1.1\" 200 1234 99999999999999999999";
+        assert!(matches!(
+            spec.parse(line),
+            Err(ParseError::InvalidDuration { value, .. }) if value == "99999999999999999999"
+        ));
+    }
+
+    #[test]
+    fn test_header_and_cookie_fields() {
+        let directive = r#"%h %t \"%r\" %>s %b \"%{X-Forwarded-For}i\" \"%{Content-Type}o\" %{session}C"#;
+        let spec = FormatSpec::from_apache_directive(directive).unwrap();
+
+        let line = r#"10.0.0.5 [15/Jan/2024:10:24:12 +0000] "GET /api HTTP/1.1" 200 1234 "192.168.0.1, 10.0.0.1" "application/json" abc123"#;
+        let log = spec.parse(line).unwrap();
+        assert_eq!(log.status, 200);
+        assert_eq!(log.fields.len(), 3);
+        assert_eq!(log.fields["X-Forwarded-For"], "192.168.0.1, 10.0.0.1");
+        assert_eq!(log.fields["Content-Type"], "application/json");
+        assert_eq!(log.fields["session"], "abc123");
+
+        // A "-" value means the header or cookie was absent.
+        let line = r#"10.0.0.5 [15/Jan/2024:10:24:12 +0000] "GET /api HTTP/1.1" 200 1234 "-" "text/html" -"#;
+        let log = spec.parse(line).unwrap();
+        assert_eq!(log.fields.keys().collect::<Vec<_>>(), ["Content-Type"]);
+
+        let duplicate = FormatSpec::from_apache_directive("%h %{session}C %{session}C");
+        assert_eq!(duplicate.unwrap_err(), FormatError::DuplicateDirective("session".to_string()));
+    }
+
+    #[test]
+    fn test_variable_fields() {
+        let directive = r#"%h %t \"%r\" %>s %b %{SSL_PROTOCOL}x %{SSL_CIPHER}x \"%{User-agent}i\""#;
+        let spec = FormatSpec::from_apache_directive(directive).unwrap();
+
+        let line = r#"10.0.0.5 [15/Jan/2024:10:24:12 +0000] "GET /api HTTP/1.1" 200 1234 TLSv1.3 TLS_AES_256_GCM_SHA384 "curl/8.0""#;
+        let log = spec.parse(line).unwrap();
+        assert_eq!(log.fields["SSL_PROTOCOL"], "TLSv1.3");
+        assert_eq!(log.fields["SSL_CIPHER"], "TLS_AES_256_GCM_SHA384");
+        assert_eq!(log.user_agent.as_deref(), Some("curl/8.0"));
+
+        // Plain HTTP requests log "-" for the TLS variables.
+        let line = r#"10.0.0.5 [15/Jan/2024:10:24:12 +0000] "GET /api HTTP/1.1" 200 1234 - - "curl/8.0""#;
+        assert!(spec.parse(line).unwrap().fields.is_empty());
+
+        assert_eq!(
+            FormatSpec::from_apache_directive("%h %x").unwrap_err(),
+            FormatError::UnknownDirective("%x".to_string())
+        );
+        assert_eq!(
+            FormatSpec::from_apache_directive("%h %{SSL_PROTOCOL}x %{SSL_PROTOCOL}i").unwrap_err(),
+            FormatError::DuplicateDirective("SSL_PROTOCOL".to_string())
+        );
+    }
+
+    #[test]
+    fn test_invalid_directives() {
+        let cases = [
+            ("%h %Q", FormatError::UnknownDirective("%Q".to_string())),
+            ("%h %{Referer", FormatError::UnterminatedBrace("%{Referer".to_string())),
+            ("%h %{Referer}z", FormatError::UnknownDirective("%{Referer}z".to_string())),
+            ("%h %", FormatError::UnknownDirective("%".to_string())),
+            ("%h %h", FormatError::DuplicateDirective("ip".to_string())),
+        ];
+
+        for (directive, expected) in cases.iter() {
+            let spec = FormatSpec::from_apache_directive(directive);
+            assert_eq!(spec.unwrap_err(), *expected, "Wrong result for directive: {directive}");
+        }
+
+        // Every directive is fine on its own, but together they exceed the compiled size limit.
+        let directive = (0..5_000).map(|i| format!("\"%{{H{i}}}i\"")).collect::<Vec<_>>().join(" ");
+        let spec = FormatSpec::from_apache_directive(&directive);
+        assert!(matches!(spec, Err(FormatError::InvalidPattern(regex::Error::CompiledTooBig(_)))));
+
+        let log = parse_with_format("10.0.0.5 x", &LogFormat::Custom("%h %Q".to_string()));
+        assert_eq!(
+            log.unwrap_err(),
+            ParseError::InvalidTemplate(FormatError::UnknownDirective("%Q".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_detect_format() {
+        let common = "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"GET /api HTTP/1.1\" 200 567";
+        let combined = "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"GET /api HTTP/1.1\" 200 567 \"-\" \"curl/8.0\"";
+
+        assert_eq!(detect_format(&[common, "", common]), LogFormat::Common);
+        assert_eq!(detect_format(&[combined, combined, common]), LogFormat::Combined);
+        assert_eq!(detect_format(&[common, common, combined]), LogFormat::Common);
+        let vhost = format!("www.example.com {combined}");
+        assert_eq!(detect_format(&[&vhost, &vhost, combined]), LogFormat::VhostCombined);
+        assert_eq!(detect_format(&[&vhost, combined, combined]), LogFormat::Combined);
+        let squid = "1705314252.123    152 10.0.0.5 TCP_MISS/200 1234 GET http://example.com/ - HIER_DIRECT/10.0.0.1 text/html";
+        assert_eq!(detect_format(&[squid, squid, common]), LogFormat::Squid);
+        assert_eq!(
+            detect_format(&["#Software: IIS", "#Fields: date time c-ip", "2024-01-15 10:24:12 10.0.0.5"]),
+            LogFormat::W3c
+        );
+        assert_eq!(detect_format(&["garbage", ""]), LogFormat::Common);
+        assert_eq!(detect_format(&[]), LogFormat::Common);
+    }
+}
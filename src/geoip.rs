@@ -0,0 +1,53 @@
+use std::net::IpAddr;
+use std::path::Path;
+
+use maxminddb::{MaxMindDbError, Reader, path};
+
+use crate::parser::LogEntry;
+
+pub struct GeoIp {
+    reader: Reader<Vec<u8>>,
+}
+
+impl GeoIp {
+    /// Opens a MaxMind GeoLite2/GeoIP2 Country or City database.
+    pub fn open(database: impl AsRef<Path>) -> Result<Self, MaxMindDbError> {
+        let reader = Reader::open_readfile(database)?;
+        Ok(GeoIp { reader })
+    }
+
+    /// ISO 3166-1 country code; `None` for private or unknown addresses.
+    pub fn country(&self, ip: IpAddr) -> Option<String> {
+        let result = self.reader.lookup(ip).ok()?;
+        result
+            .decode_path::<String>(&path!["country", "iso_code"])
+            .ok()
+            .flatten()
+    }
+
+    pub fn enrich(&self, entry: &mut LogEntry) {
+        entry.country = self.country(entry.ip);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_open_missing_database() {
+        assert!(GeoIp::open("does-not-exist.mmdb").is_err());
+    }
+
+    // Built by tests/fixtures/make_geoip_test_db.py.
+    #[test]
+    fn test_country_lookup() {
+        let geoip = GeoIp::open("tests/fixtures/geoip-country-test.mmdb").unwrap();
+        assert_eq!(geoip.country("81.2.69.170".parse().unwrap()).as_deref(), Some("GB"));
+        assert_eq!(geoip.country("10.0.0.5".parse().unwrap()), None);
+
+        let mut entry = LogEntry::builder().ip("89.160.20.120".parse::<IpAddr>().unwrap()).build();
+        geoip.enrich(&mut entry);
+        assert_eq!(entry.country.as_deref(), Some("SE"));
+    }
+}
@@ -0,0 +1,55 @@
+//! The line parsers (`parser`, `format`, `w3c` and `squid`) only touch `core` and `alloc`
+//! APIs; file access, decompression and following live in `reader` and `follow` behind
+//! the default `std` feature. The filters and reports built on `LogEntry` still use std
+//! collections, and the parsers can't be built `no_std` yet either: `regex` and the
+//! `LazyLock` holding the line patterns still need std.
+
+extern crate alloc;
+
+pub mod dedup;
+pub mod filter;
+#[cfg(feature = "std")]
+pub mod follow;
+pub mod format;
+#[cfg(feature = "geoip")]
+pub mod geoip;
+pub mod merge;
+pub mod metrics;
+#[cfg(feature = "rayon")]
+pub mod parallel;
+pub mod parser;
+#[cfg(feature = "std")]
+pub mod reader;
+pub mod report;
+pub mod session;
+pub mod squid;
+pub mod stats;
+#[cfg(feature = "tokio")]
+pub mod stream;
+pub mod w3c;
+
+pub use dedup::DedupConsecutive;
+pub use filter::{FilterError, PathFilter, StatusFilter, StatusMatcher, TimeRange, is_bot};
+#[cfg(feature = "std")]
+pub use follow::FollowReader;
+pub use format::{FormatError, FormatSpec, LogFormat, detect_format, parse_with_format};
+pub use merge::{MergeByTimestamp, merge_by_timestamp};
+pub use metrics::{Prometheus, RequestCounts};
+pub use parser::{
+    HTTP_METHODS, LogEntry, LogEntryBuilder, ParseError, ParseOptions, SizeUnits, StatusClass, TimestampFormat,
+    format_size, parse_combined_log, parse_combined_log_with, parse_common_log, parse_common_log_with, parse_many,
+    parse_vhost_combined_log, parse_vhost_combined_log_with,
+};
+#[cfg(feature = "std")]
+pub use reader::{
+    EntriesBetween, LineError, LogParser, LongLines, WithOffsets, decompress, is_compressed, open_maybe_compressed,
+};
+pub use report::{
+    ApproxQuantiles, ClientTraffic, PathRule, SizeStats, SortKey, StatusHistogram, bucket_by_interval, bytes_by_ip,
+    detect_rate_spikes, group_404s_by_referrer, hits_by_prefix, hits_by_referrer_host, normalize_path, slowest,
+    sort_entries, top_ips, top_normalized_paths, top_paths, unique_ips_per_day,
+};
+pub use session::{DEFAULT_SESSION_GAP, Session, sessionize};
+pub use squid::{parse_squid, parse_squid_with};
+pub use stats::{ErrorKindReport, ErrorReport, ParseStats};
+pub use w3c::{W3cParser, parse_w3c};
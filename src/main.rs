@@ -1,33 +1,1213 @@
-use std::{fs::File};
-use std::io::{BufReader};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io;
+use std::io::{BufReader, BufWriter};
 use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Once;
+use std::sync::atomic::Ordering;
 
-use clap::Parser;
+use chrono::{DateTime, SecondsFormat, TimeDelta, Utc};
+use clap::error::ErrorKind;
+use clap::{ArgGroup, CommandFactory, Parser, ValueEnum};
+use indicatif::{ProgressBar, ProgressStyle};
+use regex::Regex;
 
-
-pub mod parser;
-
-use crate::parser::*;
+use common_log_parser::filter::parse_timestamp;
+use common_log_parser::{
+    ApproxQuantiles, DedupConsecutive, ErrorReport, FollowReader, LineError, LogEntry, LogFormat, LogParser, LongLines,
+    ParseOptions, ParseStats, PathFilter, PathRule, Prometheus, RequestCounts, SizeStats, SizeUnits, SortKey,
+    StatusFilter, StatusHistogram, StatusMatcher, TimeRange, TimestampFormat, bucket_by_interval, decompress,
+    detect_format, detect_rate_spikes, format_size, group_404s_by_referrer, hits_by_prefix, hits_by_referrer_host,
+    is_bot, is_compressed, merge_by_timestamp, open_maybe_compressed, parse_with_format, slowest, top_ips,
+    top_normalized_paths, top_paths, unique_ips_per_day,
+};
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
+#[command(group(ArgGroup::new("path_filter").multiple(true).args(["path_contains", "path_regex"])))]
 struct Cli {
-    filename: String,
+    /// Log files or glob patterns to parse, optionally compressed; reads stdin when omitted or `-`
+    files: Vec<String>,
+
+    /// Layout of the input log lines
+    #[arg(long, value_enum, default_value_t = InputFormat::Auto)]
+    log_format: InputFormat,
+
+    /// Output format for parsed entries
+    #[arg(long, value_enum, default_value_t = OutputFormat::Debug)]
+    format: OutputFormat,
+
+    /// Comma-separated fields to emit, in order, for csv and json output (e.g. `ip,status,path`)
+    #[arg(long, value_enum, value_delimiter = ',', value_name = "FIELDS")]
+    fields: Vec<Column>,
+
+    /// Print each entry through a template of `{field}` placeholders instead, e.g. `{ip} {status} {path}`;
+    /// `{{` and `}}` are literal braces
+    #[arg(
+        long,
+        value_name = "TEMPLATE",
+        value_parser = parse_output_template,
+        conflicts_with_all = ["format", "fields"]
+    )]
+    output_template: Option<OutputTemplate>,
+
+    /// Write results to this file instead of stdout
+    #[arg(long, short, value_name = "PATH")]
+    output: Option<PathBuf>,
+
+    /// Treat request methods outside the standard HTTP verbs as malformed
+    #[arg(long)]
+    strict_methods: bool,
+
+    /// Uppercase request methods, so `get` and `GET` are counted together
+    #[arg(long)]
+    uppercase_methods: bool,
+
+    /// Timestamp layout as a strftime pattern (e.g. `%+` for ISO 8601), or `epoch` / `epoch_ms`
+    #[arg(long, value_name = "FORMAT")]
+    timestamp_format: Option<TimestampFormat>,
+
+    /// Comma-separated month abbreviations, January first, for timestamps logged in another language
+    #[arg(long, value_name = "NAMES", value_parser = parse_month_names)]
+    month_names: Option<[String; 12]>,
+
+    /// Keep each timestamp exactly as logged alongside the parsed value (JSON output)
+    #[arg(long)]
+    raw_timestamp: bool,
+
+    /// Longest line to read, in bytes; longer lines are malformed unless `--truncate-long-lines`
+    #[arg(long, value_name = "BYTES", default_value_t = 64 * 1024)]
+    max_line_length: usize,
+
+    /// Parse the first `--max-line-length` bytes of an over-long line instead of rejecting it
+    #[arg(long)]
+    truncate_long_lines: bool,
+
+    /// Report malformed lines on stderr and keep going instead of aborting
+    #[arg(long)]
+    skip_invalid: bool,
+
+    /// With `--skip-invalid`, end with a count per error kind and a few of the offending lines
+    #[arg(long, requires = "skip_invalid")]
+    error_report: bool,
+
+    /// Only check that every line parses: print nothing and exit 0 if so, otherwise exit 2
+    /// after a summary of the failures on stderr. Input that can't be read or decompressed
+    /// exits 1, as without `--validate`
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "skip_invalid", "output", "follow", "sorted", "head", "tail", "sort_by", "count", "dedup", "progress",
+        ]
+    )]
+    validate: bool,
+
+    /// Only emit entries matching a status code (`404`) or class (`5xx`); repeatable
+    #[arg(long, alias = "filter-status", value_name = "STATUS")]
+    status: Vec<StatusMatcher>,
+
+    /// Drop entries matching a status code (`200`) or class (`2xx`); repeatable. Applied after
+    /// `--status`, so `--status 4xx --exclude-status 404` keeps every 4xx but 404
+    #[arg(long, value_name = "STATUS")]
+    exclude_status: Vec<StatusMatcher>,
+
+    /// Only emit entries at or after this time (RFC3339 or CLF timestamp)
+    #[arg(long, value_parser = parse_timestamp)]
+    since: Option<DateTime<Utc>>,
+
+    /// Only emit entries at or before this time (RFC3339 or CLF timestamp)
+    #[arg(long, value_parser = parse_timestamp)]
+    until: Option<DateTime<Utc>>,
+
+    /// The input is sorted by time, so `--since` can seek into plain files instead of reading from the start
+    #[arg(long, conflicts_with = "follow")]
+    sorted: bool,
+
+    /// MaxMind GeoLite2 database used to fill in each client's country
+    #[cfg(feature = "geoip")]
+    #[arg(long, value_name = "PATH")]
+    geoip: Option<std::path::PathBuf>,
+
+    /// Only emit entries whose path contains this substring
+    #[arg(long, value_name = "SUBSTR")]
+    path_contains: Option<String>,
+
+    /// Only emit entries whose path matches this regular expression
+    #[arg(long, value_name = "RE")]
+    path_regex: Option<Regex>,
+
+    /// Emit the entries the path filters would drop instead, like `grep -v`
+    #[arg(long, alias = "invert-match", requires = "path_filter")]
+    invert: bool,
+
+    /// Drop entries whose user agent looks like a crawler or scripted client
+    #[arg(long)]
+    exclude_bots: bool,
+
+    /// Zero the last octet of IPv4 and the last 80 bits of IPv6 client addresses in all output
+    #[arg(long)]
+    anonymize_ip: bool,
+
+    /// Collapse consecutive repeats of the same ip, method, path and status into one entry with a count
+    #[arg(long)]
+    dedup: bool,
+
+    /// Keep reading lines appended to a single input file, like `tail -f`; Ctrl-C stops
+    #[arg(long)]
+    follow: bool,
+
+    /// How often `--follow` checks for new data, e.g. `500ms`, `2s`
+    #[arg(long, value_name = "INTERVAL", value_parser = parse_interval, default_value = "1s", requires = "follow")]
+    poll_interval: TimeDelta,
+
+    /// Show a progress bar on stderr; inputs of unknown size get a line counter instead
+    #[arg(long, conflicts_with = "follow")]
+    progress: bool,
+
+    /// Interleave the inputs by timestamp instead of reading them one after another; each
+    /// input must already be in time order
+    #[arg(long, conflicts_with_all = ["follow", "progress"])]
+    merge: bool,
+
+    /// Stop after the first N entries that pass the filters
+    #[arg(long, value_name = "N", conflicts_with = "tail")]
+    head: Option<usize>,
+
+    /// Keep only the last N entries that pass the filters; holds at most N entries in memory
+    #[arg(long, value_name = "N", conflicts_with = "follow")]
+    tail: Option<usize>,
+
+    /// Order entries by a field; buffers every entry in memory before printing
+    #[arg(long, value_enum, value_name = "FIELD")]
+    sort_by: Option<SortField>,
+
+    /// Reverse the `--sort-by` order, e.g. to list the largest responses first
+    #[arg(long, requires = "sort_by")]
+    reverse: bool,
+
+    /// Print only the number of entries left after filtering
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "sort_by", "summary", "top_paths", "top_ips", "slowest", "prometheus", "unique_visitors", "rate",
+            "bandwidth", "broken_links", "referrers", "group_by_prefix", "rate_limit_report",
+        ]
+    )]
+    count: bool,
+
+    /// Print a count per status code instead of the entries themselves
+    #[arg(long)]
+    summary: bool,
+
+    /// Print the N most requested paths with their hit counts
+    #[arg(long, value_name = "N")]
+    top_paths: Option<usize>,
+
+    /// Count `--top-paths` with numeric IDs, UUIDs and long hex segments replaced by `{id}`,
+    /// `{uuid}` and `{hash}`, so `/users/42` and `/users/43` are both `/users/{id}`
+    #[arg(long, requires = "top_paths")]
+    normalize_paths: bool,
+
+    /// A `--normalize-paths` rule replacing whole path segments matching RE with PLACEHOLDER,
+    /// e.g. `[a-z]{2}-[A-Z]{2}={locale}`; repeatable, tried in order before the built-in rules
+    #[arg(long, value_name = "RE=PLACEHOLDER", value_parser = parse_path_rule, requires = "normalize_paths")]
+    path_rule: Vec<PathRule>,
+
+    /// Print hit counts by the first N path segments, e.g. `/api` and `/static` for N = 1
+    #[arg(long, value_name = "N")]
+    group_by_prefix: Option<usize>,
+
+    /// Print total bytes served, the average response size and its p50/p95/p99; the
+    /// percentiles are estimated to within 1% when following or reading stdin
+    #[arg(long)]
+    bandwidth: bool,
+
+    /// Print each path that returned 404 with the referrers linking to it; needs combined logs
+    #[arg(long)]
+    broken_links: bool,
+
+    /// Print hit counts by referring domain, with `-` for requests without one; needs combined logs
+    #[arg(long)]
+    referrers: bool,
+
+    /// Print the N clients that transferred the most bytes
+    #[arg(long, value_name = "N")]
+    top_ips: Option<usize>,
+
+    /// Print request counts by status class and method in Prometheus text format
+    #[arg(long)]
+    prometheus: bool,
+
+    /// Print the N requests that took longest to serve; needs a format that logs durations
+    #[arg(long, value_name = "N")]
+    slowest: Option<usize>,
+
+    /// Print the number of distinct client IPs per UTC day
+    #[arg(long)]
+    unique_visitors: bool,
+
+    /// Print request counts per time bucket, e.g. `30s`, `1m`, `1h`, `1d`
+    #[arg(long, value_name = "INTERVAL", value_parser = parse_interval)]
+    rate: Option<TimeDelta>,
+
+    /// Print each client that sent more than N requests within INTERVAL, e.g. `100/60s`, with
+    /// the time it crossed the limit
+    #[arg(long, value_name = "N/INTERVAL", value_parser = parse_rate_limit)]
+    rate_limit_report: Option<(usize, TimeDelta)>,
 }
 
-fn main() -> Result<(), anyhow::Error> {
-    let args = Cli::parse();
+impl Cli {
+    fn wants_report(&self) -> bool {
+        self.bandwidth || self.wants_buffered_report()
+    }
+
+    // Reports that need every entry; `--bandwidth` can get by without, see `approximate_bandwidth`.
+    fn wants_buffered_report(&self) -> bool {
+        self.summary
+            || self.prometheus
+            || self.unique_visitors
+            || self.broken_links
+            || self.referrers
+            || self.top_paths.is_some()
+            || self.group_by_prefix.is_some()
+            || self.top_ips.is_some()
+            || self.slowest.is_some()
+            || self.rate.is_some()
+            || self.rate_limit_report.is_some()
+    }
+
+    // Input that may never end gets approximate `--bandwidth` percentiles in bounded memory,
+    // unless something else needs every entry anyway.
+    fn approximate_bandwidth(&self) -> bool {
+        let unbounded = self.follow || self.files.is_empty() || self.files.iter().any(|file| file == "-");
+        self.bandwidth && unbounded && !self.wants_buffered_report() && self.sort_by.is_none() && self.tail.is_none()
+    }
+}
+
+fn parse_month_names(s: &str) -> Result<[String; 12], String> {
+    let names = s.split(',').map(|name| name.trim().to_string()).collect::<Vec<_>>();
+    names
+        .try_into()
+        .map_err(|names: Vec<_>| format!("expected 12 month names, found {}", names.len()))
+}
+
+fn parse_interval(s: &str) -> Result<TimeDelta, String> {
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (amount, unit) = s.split_at(split);
+    let amount = amount.parse::<i64>().map_err(|_| format!("invalid interval {:?}", s))?;
+
+    let interval = match unit {
+        "ms" => TimeDelta::try_milliseconds(amount),
+        "s" => TimeDelta::try_seconds(amount),
+        "m" => TimeDelta::try_minutes(amount),
+        "h" => TimeDelta::try_hours(amount),
+        "d" => TimeDelta::try_days(amount),
+        _ => return Err(format!("invalid interval unit {:?}, expected ms, s, m, h or d", unit)),
+    };
+    interval
+        .filter(|interval| *interval > TimeDelta::zero())
+        .ok_or_else(|| format!("invalid interval {:?}", s))
+}
+
+// `[0-9]+={id}`; the placeholder follows the last `=`, as a regex may contain one.
+fn parse_path_rule(s: &str) -> Result<PathRule, String> {
+    let (pattern, placeholder) = s
+        .rsplit_once('=')
+        .ok_or_else(|| format!("invalid path rule {:?}, expected RE=PLACEHOLDER", s))?;
+    PathRule::new(pattern, placeholder).map_err(|err| err.to_string())
+}
+
+// `100/60s`: at most 100 requests in any 60 seconds.
+fn parse_rate_limit(s: &str) -> Result<(usize, TimeDelta), String> {
+    let (requests, interval) = s
+        .split_once('/')
+        .ok_or_else(|| format!("invalid rate limit {:?}, expected N/INTERVAL", s))?;
+    let requests = requests
+        .parse::<usize>()
+        .map_err(|_| format!("invalid request count {:?}", requests))?;
+    Ok((requests, parse_interval(interval)?))
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum InputFormat {
+    /// Guess from the first lines of each input
+    Auto,
+    /// NCSA common log format
+    Common,
+    /// Common log format with trailing referrer and user agent
+    Combined,
+    /// Combined log format behind a virtual host column (`%v`)
+    VhostCombined,
+    /// W3C extended log format, as written by IIS
+    W3c,
+    /// Squid's native access.log format
+    Squid,
+}
+
+// Offending lines kept per error kind by `--error-report`.
+const ERROR_EXAMPLES: usize = 3;
+
+// Lines sniffed by `--log-format auto`, and to check an explicit format against.
+const DETECT_SAMPLE_LINES: usize = 20;
+
+// Warn about a format mismatch once per run, not once per input.
+static FORMAT_MISMATCH: Once = Once::new();
+
+impl InputFormat {
+    // Detection peeks at the buffered start of the input without consuming it. An explicit
+    // format is still used as given, with a warning if the input looks like another one.
+    fn resolve(self, input: &mut dyn BufRead) -> io::Result<LogFormat> {
+        let sample = String::from_utf8_lossy(input.fill_buf()?);
+        let lines = sample.lines().take(DETECT_SAMPLE_LINES).collect::<Vec<_>>();
+        let detected = detect_format(&lines);
+        let requested = match self {
+            InputFormat::Auto => return Ok(detected),
+            InputFormat::Common => LogFormat::Common,
+            InputFormat::Combined => LogFormat::Combined,
+            InputFormat::VhostCombined => LogFormat::VhostCombined,
+            InputFormat::W3c => LogFormat::W3c,
+            InputFormat::Squid => LogFormat::Squid,
+        };
+
+        // Detection falls back to common when nothing parses, which says nothing about the input.
+        let recognized =
+            detected == LogFormat::W3c || lines.iter().any(|line| parse_with_format(line, &detected).is_ok());
+        if requested != detected && recognized {
+            FORMAT_MISMATCH.call_once(|| {
+                eprintln!(
+                    "warning: lines appear to be {} format but {} was requested; they may fail to parse",
+                    format_name(&detected),
+                    format_name(&requested)
+                )
+            });
+        }
+        Ok(requested)
+    }
+}
+
+// The `--log-format` value for `format`.
+fn format_name(format: &LogFormat) -> &'static str {
+    match format {
+        LogFormat::Common => "common",
+        LogFormat::Combined => "combined",
+        LogFormat::VhostCombined => "vhost-combined",
+        LogFormat::Custom(_) => "custom",
+        LogFormat::W3c => "w3c",
+        LogFormat::Squid => "squid",
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum SortField {
+    Timestamp,
+    Status,
+    Size,
+    Ip,
+}
+
+impl From<SortField> for SortKey {
+    fn from(field: SortField) -> Self {
+        match field {
+            SortField::Timestamp => SortKey::Timestamp,
+            SortField::Status => SortKey::Status,
+            SortField::Size => SortKey::Size,
+            SortField::Ip => SortKey::Ip,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum OutputFormat {
+    /// Rust debug representation
+    Debug,
+    /// One JSON object per line (NDJSON)
+    Json,
+    /// A single JSON array of entry objects, written as it goes
+    JsonArray,
+    /// Comma-separated values with a header row
+    Csv,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+#[value(rename_all = "snake_case")]
+enum Column {
+    Vhost,
+    Ip,
+    Identity,
+    User,
+    Timestamp,
+    Method,
+    Host,
+    Path,
+    Query,
+    Protocol,
+    Status,
+    Size,
+    Referrer,
+    UserAgent,
+    Country,
+    Duration,
+}
+
+// The CSV columns without `--fields`.
+const DEFAULT_COLUMNS: [Column; 6] = [
+    Column::Ip,
+    Column::Timestamp,
+    Column::Method,
+    Column::Path,
+    Column::Status,
+    Column::Size,
+];
+
+impl Column {
+    // The CSV header, matching the field's key in JSON output.
+    fn name(self) -> &'static str {
+        match self {
+            Column::Vhost => "vhost",
+            Column::Ip => "ip",
+            Column::Identity => "identity",
+            Column::User => "user",
+            Column::Timestamp => "timestamp",
+            Column::Method => "method",
+            Column::Host => "host",
+            Column::Path => "path",
+            Column::Query => "query",
+            Column::Protocol => "protocol",
+            Column::Status => "status",
+            Column::Size => "size",
+            Column::Referrer => "referrer",
+            Column::UserAgent => "user_agent",
+            Column::Country => "country",
+            Column::Duration => "duration",
+        }
+    }
 
-    println!("filename: {}", args.filename);
-    let file = File::open(args.filename)?;
-    let buf = BufReader::new(file);
+    // Missing optional fields are empty; durations are in seconds.
+    fn csv_value(self, entry: &LogEntry) -> String {
+        let optional = |value: &Option<String>| value.clone().unwrap_or_default();
+        match self {
+            Column::Vhost => optional(&entry.vhost),
+            Column::Ip => entry.ip.to_string(),
+            Column::Identity => optional(&entry.identity),
+            Column::User => optional(&entry.user),
+            Column::Timestamp => entry.timestamp.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+            Column::Method => entry.method.clone(),
+            Column::Host => optional(&entry.host),
+            Column::Path => entry.path.clone(),
+            Column::Query => optional(&entry.query),
+            Column::Protocol => entry.protocol.clone(),
+            Column::Status => entry.status.to_string(),
+            Column::Size => entry.size.to_string(),
+            Column::Referrer => optional(&entry.referrer),
+            Column::UserAgent => optional(&entry.user_agent),
+            Column::Country => optional(&entry.country),
+            Column::Duration => entry.duration.map(|d| d.as_secs_f64().to_string()).unwrap_or_default(),
+        }
+    }
+}
 
-    for line in buf.lines() {
-        let line = line?;
-        let log_entry = parse_common_log(&line)?;
+#[derive(Clone)]
+enum TemplatePart {
+    Literal(String),
+    Column(Column),
+}
 
-        println!("{:?}", log_entry);
+#[derive(Clone)]
+struct OutputTemplate(Vec<TemplatePart>);
+
+impl OutputTemplate {
+    // Placeholders render like CSV values.
+    fn render(&self, entry: &LogEntry) -> String {
+        self.0
+            .iter()
+            .map(|part| match part {
+                TemplatePart::Literal(literal) => literal.clone(),
+                TemplatePart::Column(column) => column.csv_value(entry),
+            })
+            .collect()
     }
+}
 
+// "{ip} - {status}" -> [Column(Ip), Literal(" - "), Column(Status)]
+fn parse_output_template(s: &str) -> Result<OutputTemplate, String> {
+    let mut parts = Vec::new();
+    let mut literal = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.as_str().starts_with('{') => {
+                chars.next();
+                literal.push('{');
+            }
+            '}' if chars.as_str().starts_with('}') => {
+                chars.next();
+                literal.push('}');
+            }
+            '{' => {
+                let (name, rest) = chars
+                    .as_str()
+                    .split_once('}')
+                    .ok_or_else(|| format!("unterminated placeholder in {:?}", s))?;
+                let column = Column::from_str(name, false).map_err(|_| format!("unknown placeholder {{{}}}", name))?;
+                if !literal.is_empty() {
+                    parts.push(TemplatePart::Literal(std::mem::take(&mut literal)));
+                }
+                parts.push(TemplatePart::Column(column));
+                chars = rest.chars();
+            }
+            '}' => return Err(format!("unmatched }} in {:?}, write }}}} for a literal brace", s)),
+            c => literal.push(c),
+        }
+    }
+    if !literal.is_empty() {
+        parts.push(TemplatePart::Literal(literal));
+    }
+    Ok(OutputTemplate(parts))
+}
+
+fn csv_record(entry: &LogEntry, columns: &[Column]) -> Vec<String> {
+    columns.iter().map(|column| column.csv_value(entry)).collect()
+}
+
+// Writes the whole entry, or just `columns` in the order given, which a `serde_json::Map`
+// would sort instead.
+fn write_json(out: &mut impl Write, entry: &LogEntry, columns: &[Column], count: Option<usize>) -> io::Result<()> {
+    let mut value = serde_json::to_value(entry)?;
+    if columns.is_empty() {
+        if let Some(count) = count {
+            value["count"] = count.into();
+        }
+        return Ok(serde_json::to_writer(out, &value)?);
+    }
+
+    out.write_all(b"{")?;
+    for (i, column) in columns.iter().enumerate() {
+        if i > 0 {
+            out.write_all(b",")?;
+        }
+        write!(out, "\"{}\":", column.name())?;
+        serde_json::to_writer(&mut *out, &value[column.name()])?;
+    }
+    if let Some(count) = count {
+        write!(out, ",\"count\":{count}")?;
+    }
+    out.write_all(b"}")
+}
+
+enum EntryWriter<W: Write> {
+    Debug(W),
+    Json { out: W, columns: Vec<Column> },
+    JsonArray { out: W, columns: Vec<Column>, empty: bool },
+    Csv { writer: Box<csv::Writer<W>>, columns: Vec<Column> },
+    Template { out: W, template: OutputTemplate },
+}
+
+// Each element brings its own leading separator, so the array never ends in a comma.
+fn start_element(out: &mut impl Write, empty: &mut bool) -> io::Result<()> {
+    out.write_all(if *empty { b"\n" } else { b",\n" })?;
+    *empty = false;
     Ok(())
 }
+
+impl<W: Write> EntryWriter<W> {
+    // `counted` adds a trailing count column to CSV output for `write_repeated`. Empty
+    // `columns` mean every field for JSON and `DEFAULT_COLUMNS` for CSV.
+    fn new(format: OutputFormat, mut out: W, columns: &[Column], counted: bool) -> Result<Self, anyhow::Error> {
+        let columns = columns.to_vec();
+        let writer = match format {
+            OutputFormat::Debug => EntryWriter::Debug(out),
+            OutputFormat::Json => EntryWriter::Json { out, columns },
+            OutputFormat::JsonArray => {
+                out.write_all(b"[")?;
+                EntryWriter::JsonArray {
+                    out,
+                    columns,
+                    empty: true,
+                }
+            }
+            OutputFormat::Csv => {
+                let columns = if columns.is_empty() {
+                    DEFAULT_COLUMNS.to_vec()
+                } else {
+                    columns
+                };
+                let mut writer = csv::Writer::from_writer(out);
+                let header = columns.iter().map(|column| column.name());
+                if counted {
+                    writer.write_record(header.chain(["count"]))?;
+                } else {
+                    writer.write_record(header)?;
+                }
+                EntryWriter::Csv {
+                    writer: Box::new(writer),
+                    columns,
+                }
+            }
+        };
+        Ok(writer)
+    }
+
+    fn write(&mut self, entry: &LogEntry) -> Result<(), anyhow::Error> {
+        match self {
+            EntryWriter::Debug(out) => writeln!(out, "{:?}", entry)?,
+            EntryWriter::Json { out, columns } if columns.is_empty() => {
+                serde_json::to_writer(&mut *out, entry)?;
+                writeln!(out)?;
+            }
+            EntryWriter::Json { out, columns } => {
+                write_json(out, entry, columns, None)?;
+                writeln!(out)?;
+            }
+            EntryWriter::JsonArray { out, columns, empty } => {
+                start_element(out, empty)?;
+                write_json(out, entry, columns, None)?;
+            }
+            EntryWriter::Csv { writer, columns } => writer.write_record(csv_record(entry, columns))?,
+            EntryWriter::Template { out, template } => writeln!(out, "{}", template.render(entry))?,
+        }
+        Ok(())
+    }
+
+    fn write_repeated(&mut self, entry: &LogEntry, count: usize) -> Result<(), anyhow::Error> {
+        match self {
+            EntryWriter::Debug(out) if count > 1 => writeln!(out, "{:?} (x{})", entry, count)?,
+            EntryWriter::Template { out, template } if count > 1 => {
+                writeln!(out, "{} (x{})", template.render(entry), count)?
+            }
+            EntryWriter::Debug(_) | EntryWriter::Template { .. } => self.write(entry)?,
+            EntryWriter::Json { out, columns } => {
+                write_json(out, entry, columns, Some(count))?;
+                writeln!(out)?;
+            }
+            EntryWriter::JsonArray { out, columns, empty } => {
+                start_element(out, empty)?;
+                write_json(out, entry, columns, Some(count))?;
+            }
+            EntryWriter::Csv { writer, columns } => {
+                let mut record = csv_record(entry, columns);
+                record.push(count.to_string());
+                writer.write_record(record)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_filename(&mut self, filename: &str) -> Result<(), anyhow::Error> {
+        if let EntryWriter::Debug(out) = self {
+            writeln!(out, "filename: {}", filename)?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), anyhow::Error> {
+        match self {
+            EntryWriter::Debug(out)
+            | EntryWriter::Json { out, .. }
+            | EntryWriter::JsonArray { out, .. }
+            | EntryWriter::Template { out, .. } => out.flush()?,
+            EntryWriter::Csv { writer, .. } => writer.flush()?,
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<(), anyhow::Error> {
+        if let EntryWriter::JsonArray { out, empty, .. } = &mut self {
+            out.write_all(if *empty { b"]\n" } else { b"\n]\n" })?;
+        }
+        self.flush()
+    }
+}
+
+// Byte progress for files, counting compressed bytes; stdin only counts lines.
+fn progress_bar(filename: &str) -> Result<ProgressBar, anyhow::Error> {
+    let bar = if filename != "-" {
+        let style = ProgressStyle::with_template("{msg} {wide_bar} {binary_bytes}/{binary_total_bytes} ({eta})")?;
+        ProgressBar::new(fs::metadata(filename)?.len()).with_style(style)
+    } else {
+        ProgressBar::new_spinner().with_style(ProgressStyle::with_template("{spinner} {msg} {human_pos} lines")?)
+    };
+    Ok(bar.with_message(filename.to_string()))
+}
+
+// Buffered either way: stdout alone flushes on every newline.
+fn open_output(args: &Cli) -> Result<Box<dyn Write>, anyhow::Error> {
+    Ok(match &args.output {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    })
+}
+
+fn open_input(args: &Cli, filename: &str, progress: Option<&ProgressBar>) -> Result<Box<dyn BufRead>, anyhow::Error> {
+    if let Some(bar) = progress.filter(|_| filename != "-") {
+        return Ok(decompress(bar.wrap_read(File::open(filename)?), Some(Path::new(filename)))?);
+    }
+    if args.follow {
+        let follower = FollowReader::open(filename, args.poll_interval.to_std()?)?;
+        let stop = follower.stop_handle();
+        ctrlc::set_handler(move || stop.store(true, Ordering::Relaxed))?;
+        return Ok(Box::new(BufReader::new(follower)));
+    }
+    // Piped input has no extension to go by, so compression is sniffed from its first bytes.
+    if filename == "-" {
+        return Ok(decompress(io::stdin().lock(), None)?);
+    }
+
+    Ok(open_maybe_compressed(filename)?)
+}
+
+// Seeking needs the file itself, so stdin, compressed input and `--progress` read linearly.
+fn open_sorted(args: &Cli, filename: &str, progress: Option<&ProgressBar>) -> io::Result<Option<BufReader<File>>> {
+    if !args.sorted || filename == "-" || progress.is_some() {
+        return Ok(None);
+    }
+    let mut file = BufReader::new(File::open(filename)?);
+    if is_compressed(&mut file, Some(Path::new(filename)))? {
+        return Ok(None);
+    }
+    Ok(Some(file))
+}
+
+// Expand patterns the shell left alone (e.g. quoted, or on Windows).
+fn expand_inputs(files: &[String]) -> Result<Vec<String>, anyhow::Error> {
+    if files.is_empty() {
+        return Ok(vec!["-".to_string()]);
+    }
+
+    let mut inputs = Vec::new();
+    for file in files {
+        let is_pattern = file.contains(['*', '?', '[']);
+        if !is_pattern || Path::new(file).exists() {
+            inputs.push(file.clone());
+            continue;
+        }
+
+        let matches = glob::glob(file)?
+            .map(|path| path.map(|path| path.display().to_string()))
+            .collect::<Result<Vec<_>, _>>()?;
+        if matches.is_empty() {
+            anyhow::bail!("no files match pattern {file}");
+        }
+        inputs.extend(matches);
+    }
+    Ok(inputs)
+}
+
+fn print_reports(args: &Cli, entries: &[LogEntry], out: &mut impl Write) -> Result<(), anyhow::Error> {
+    if args.summary {
+        let mut histogram = StatusHistogram::new();
+        for entry in entries {
+            histogram.add(entry);
+        }
+        write!(out, "{}", histogram)?;
+    }
+    if let Some(n) = args.top_paths {
+        let ranked = if args.normalize_paths {
+            let rules = args.path_rule.iter().cloned().chain(PathRule::defaults()).collect::<Vec<_>>();
+            top_normalized_paths(entries, n, &rules)
+        } else {
+            top_paths(entries, n)
+        };
+        for (path, count) in ranked {
+            writeln!(out, "{}: {}", path, count)?;
+        }
+    }
+    if let Some(depth) = args.group_by_prefix {
+        for (prefix, count) in hits_by_prefix(entries, depth) {
+            writeln!(out, "{}: {}", prefix, count)?;
+        }
+    }
+    if args.bandwidth {
+        write!(out, "{}", entries.iter().collect::<SizeStats>())?;
+    }
+    // /old.png: 3
+    //   https://example.com/: 2
+    //   -: 1
+    if args.broken_links {
+        for (path, referrers) in group_404s_by_referrer(entries) {
+            writeln!(out, "{}: {}", path, referrers.values().sum::<usize>())?;
+            for (referrer, count) in referrers {
+                writeln!(out, "  {}: {}", referrer.as_deref().unwrap_or("-"), count)?;
+            }
+        }
+    }
+    if args.referrers {
+        for (host, count) in hits_by_referrer_host(entries) {
+            writeln!(out, "{}: {}", host.as_deref().unwrap_or("-"), count)?;
+        }
+    }
+    if let Some(n) = args.top_ips {
+        for (ip, traffic) in top_ips(entries, n) {
+            let bytes = format_size(traffic.bytes, SizeUnits::Binary);
+            writeln!(out, "{}: {}, {} requests", ip, bytes, traffic.requests)?;
+        }
+    }
+    if args.prometheus {
+        let counts = entries.iter().collect::<RequestCounts>();
+        write!(out, "{}", Prometheus(&counts))?;
+    }
+    if let Some(n) = args.slowest {
+        for entry in slowest(entries, n) {
+            let duration = entry.duration.unwrap_or_default();
+            writeln!(out, "{:?} {} {} {}", duration, entry.method, entry.path, entry.status)?;
+        }
+    }
+    if args.unique_visitors {
+        for (day, visitors) in unique_ips_per_day(entries) {
+            writeln!(out, "{} {}", day, visitors)?;
+        }
+    }
+    if let Some(interval) = args.rate {
+        for (bucket, count) in bucket_by_interval(entries, interval) {
+            writeln!(out, "{} {}", bucket.to_rfc3339_opts(SecondsFormat::AutoSi, true), count)?;
+        }
+    }
+    if let Some((threshold, window)) = args.rate_limit_report {
+        // Entries from several inputs can interleave, but each client must be in time order.
+        let mut by_time = entries.iter().collect::<Vec<_>>();
+        by_time.sort_by_key(|entry| entry.timestamp);
+        for (ip, at) in detect_rate_spikes(by_time, window, threshold) {
+            writeln!(out, "{} {}", at.to_rfc3339_opts(SecondsFormat::AutoSi, true), ip)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_run(
+    writer: &mut EntryWriter<impl Write>,
+    args: &Cli,
+    entry: &LogEntry,
+    count: usize,
+) -> Result<(), anyhow::Error> {
+    if args.dedup {
+        writer.write_repeated(entry, count)?;
+    } else {
+        writer.write(entry)?;
+    }
+    // Followed output should show up as it arrives rather than when the buffer fills.
+    if args.follow {
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+fn keep_entry(args: &Cli, filters: &Filters, entry: &LogEntry) -> bool {
+    let in_range = filters.time_range.contains(&entry.timestamp.to_utc());
+    if !filters.status_filter.matches(entry.status) || !in_range || !filters.path_filter.matches(entry) {
+        return false;
+    }
+    !(args.exclude_bots && entry.user_agent.as_deref().is_some_and(is_bot))
+}
+
+fn configure_parser<R: BufRead>(args: &Cli, parser: LogParser<R>) -> LogParser<R> {
+    let options = ParseOptions {
+        strict_methods: args.strict_methods,
+        uppercase_methods: args.uppercase_methods,
+        timestamp_format: args.timestamp_format.clone().unwrap_or_default(),
+        keep_raw_timestamp: args.raw_timestamp,
+        month_names: args.month_names.clone(),
+        ..Default::default()
+    };
+    let long_lines = if args.truncate_long_lines {
+        LongLines::Truncate
+    } else {
+        LongLines::Reject
+    };
+    parser
+        .with_options(options)
+        .with_max_line_length(args.max_line_length, long_lines)
+}
+
+// Status, time, path and bot filters plus optional enrichment, shared by every input.
+struct Filters {
+    status_filter: StatusFilter,
+    time_range: TimeRange,
+    path_filter: PathFilter,
+    #[cfg(feature = "geoip")]
+    geoip: Option<common_log_parser::geoip::GeoIp>,
+}
+
+// Parses, enriches and filters one input. Stats, skipped lines and the first error are
+// collected through `RefCell`s so `--merge` can read several inputs at once.
+fn kept_entries<'a>(
+    args: &'a Cli,
+    filters: &'a Filters,
+    filename: &'a str,
+    progress: Option<&'a ProgressBar>,
+    stats: &'a RefCell<ParseStats>,
+    skipped: &'a RefCell<ErrorReport>,
+    error: &'a RefCell<Option<LineError>>,
+) -> Result<impl Iterator<Item = LogEntry> + 'a, anyhow::Error> {
+    let parsed: Box<dyn Iterator<Item = Result<LogEntry, LineError>>> = match open_sorted(args, filename, progress)? {
+        Some(mut file) => {
+            let log_format = args.log_format.resolve(&mut file)?;
+            let parser = configure_parser(args, LogParser::with_format(file, &log_format)?);
+            Box::new(parser.entries_between(filters.time_range)?)
+        }
+        None => {
+            let mut buf = open_input(args, filename, progress)?;
+            let log_format = args.log_format.resolve(&mut buf)?;
+            Box::new(configure_parser(args, LogParser::with_format(buf, &log_format)?))
+        }
+    };
+
+    // Stops at the first malformed line unless `--skip-invalid` is set.
+    let parsed = parsed
+        .inspect(move |_| {
+            if let Some(bar) = progress.filter(|bar| bar.length().is_none()) {
+                bar.inc(1);
+            }
+        })
+        .map_while(move |result| match result {
+            Ok(log_entry) => {
+                stats.borrow_mut().record_parsed();
+                Some(Some(log_entry))
+            }
+            Err(err) if args.validate => {
+                stats.borrow_mut().record_error(&err.kind);
+                skipped.borrow_mut().record(&err.kind, err.line_number, &err.raw);
+                Some(None)
+            }
+            Err(err) if args.skip_invalid => {
+                let report = || eprintln!("{}: {}", filename, err);
+                match progress {
+                    Some(bar) => bar.suspend(report),
+                    None => report(),
+                }
+                stats.borrow_mut().record_error(&err.kind);
+                if args.error_report {
+                    skipped.borrow_mut().record(&err.kind, err.line_number, &err.raw);
+                }
+                Some(None)
+            }
+            Err(err) => {
+                error.borrow_mut().get_or_insert(err);
+                None
+            }
+        })
+        .flatten();
+    #[cfg(feature = "geoip")]
+    let parsed = parsed.map(|mut log_entry| {
+        if let Some(geoip) = &filters.geoip {
+            geoip.enrich(&mut log_entry);
+        }
+        log_entry
+    });
+    // After the GeoIP lookup, which needs the full address.
+    let parsed = parsed.map(|mut log_entry| {
+        if args.anonymize_ip {
+            log_entry.ip = log_entry.anonymized_ip();
+        }
+        log_entry
+    });
+    Ok(parsed.filter(|log_entry| keep_entry(args, filters, log_entry)))
+}
+
+// Where kept entries go: straight to the writer, or buffered for sorting, reports and
+// `--tail`, or just counted.
+struct Sink {
+    writer: Option<EntryWriter<Box<dyn Write>>>,
+    buffered: bool,
+    entries: VecDeque<(LogEntry, usize)>,
+    sizes: Option<ApproxQuantiles>,
+    count: usize,
+    taken: usize,
+}
+
+impl Sink {
+    fn new(args: &Cli) -> Result<Self, anyhow::Error> {
+        let writer = if args.count || args.wants_report() || args.validate {
+            None
+        } else {
+            let out = open_output(args)?;
+            match &args.output_template {
+                Some(template) => Some(EntryWriter::Template {
+                    out,
+                    template: template.clone(),
+                }),
+                None => Some(EntryWriter::new(args.format, out, &args.fields, args.dedup)?),
+            }
+        };
+        Ok(Sink {
+            writer,
+            // Sorting, reports and `--tail` need every entry up front; plain output streams as it goes.
+            buffered: args.sort_by.is_some() || args.wants_buffered_report() || args.tail.is_some(),
+            entries: VecDeque::new(),
+            sizes: args.approximate_bandwidth().then(ApproxQuantiles::new),
+            count: 0,
+            taken: 0,
+        })
+    }
+
+    fn is_full(&self, args: &Cli) -> bool {
+        args.head.is_some_and(|n| self.taken >= n)
+    }
+
+    fn write_filename(&mut self, args: &Cli, filename: &str) -> Result<(), anyhow::Error> {
+        if let (OutputFormat::Debug, Some(writer), false) = (args.format, &mut self.writer, self.buffered) {
+            writer.write_filename(filename)?;
+        }
+        Ok(())
+    }
+
+    fn extend(&mut self, args: &Cli, kept: impl Iterator<Item = LogEntry>) -> Result<(), anyhow::Error> {
+        let runs: Box<dyn Iterator<Item = (LogEntry, usize)>> = if args.dedup {
+            Box::new(DedupConsecutive::new(kept))
+        } else {
+            Box::new(kept.map(|log_entry| (log_entry, 1)))
+        };
+        // `take` stops reading the input as soon as `--head` is satisfied.
+        for (log_entry, repeats) in runs.take(args.head.map_or(usize::MAX, |n| n - self.taken)) {
+            self.taken += 1;
+            if args.validate {
+                continue;
+            }
+            if let Some(sizes) = &mut self.sizes {
+                sizes.add(log_entry.size);
+                continue;
+            }
+            match &mut self.writer {
+                _ if args.count => self.count += 1,
+                Some(writer) if !self.buffered => write_run(writer, args, &log_entry, repeats)?,
+                _ => {
+                    self.entries.push_back((log_entry, repeats));
+                    if args.tail.is_some_and(|n| self.entries.len() > n) {
+                        self.entries.pop_front();
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(mut self, args: &Cli) -> Result<(), anyhow::Error> {
+        if args.validate {
+            return Ok(());
+        }
+        if let Some(key) = args.sort_by {
+            let compare = SortKey::from(key).comparator(args.reverse);
+            self.entries.make_contiguous().sort_by(|(a, _), (b, _)| compare(a, b));
+        }
+        match self.writer {
+            Some(mut writer) => {
+                for (log_entry, repeats) in &self.entries {
+                    write_run(&mut writer, args, log_entry, *repeats)?;
+                }
+                writer.finish()?;
+            }
+            None => {
+                let mut out = open_output(args)?;
+                if args.count {
+                    writeln!(out, "{}", args.tail.map_or(self.count, |n| self.count.min(n)))?;
+                } else if let Some(sizes) = &self.sizes {
+                    write!(out, "{}", sizes)?;
+                } else {
+                    let entries = self.entries.into_iter().map(|(log_entry, _)| log_entry).collect::<Vec<_>>();
+                    print_reports(args, &entries, &mut out)?;
+                }
+                out.flush()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn main() -> Result<ExitCode, anyhow::Error> {
+    let args = Cli::parse();
+    let inputs = expand_inputs(&args.files)?;
+    if args.follow && (inputs.len() != 1 || inputs[0] == "-") {
+        anyhow::bail!("--follow needs exactly one input file");
+    }
+    // clap can't make an argument conflict with one value of another, so report it as its own
+    // usage error.
+    if !args.fields.is_empty() && matches!(args.format, OutputFormat::Debug) {
+        Cli::command()
+            .error(ErrorKind::ArgumentConflict, "--fields needs `--format csv` or a json format")
+            .exit();
+    }
+    if args.validate && args.wants_report() {
+        anyhow::bail!("--validate doesn't print reports");
+    }
+    let mut sink = Sink::new(&args)?;
+    let stats = RefCell::new(ParseStats::new());
+    let skipped = RefCell::new(ErrorReport::new(ERROR_EXAMPLES));
+    let error = RefCell::new(None);
+    let filters = Filters {
+        status_filter: StatusFilter::new(args.status.clone(), args.exclude_status.clone()),
+        time_range: TimeRange::new(args.since, args.until),
+        path_filter: PathFilter::new(args.path_contains.clone(), args.path_regex.clone(), args.invert),
+        #[cfg(feature = "geoip")]
+        geoip: args.geoip.as_ref().map(common_log_parser::geoip::GeoIp::open).transpose()?,
+    };
+
+    if args.merge {
+        let streams = inputs
+            .iter()
+            .map(|filename| kept_entries(&args, &filters, filename, None, &stats, &skipped, &error))
+            .collect::<Result<Vec<_>, _>>()?;
+        let merged = merge_by_timestamp(streams).take_while(|_| error.borrow().is_none());
+        sink.extend(&args, merged)?;
+    } else {
+        for filename in &inputs {
+            if sink.is_full(&args) {
+                break;
+            }
+            sink.write_filename(&args, filename)?;
+            let progress = args.progress.then(|| progress_bar(filename)).transpose()?;
+            let kept = kept_entries(&args, &filters, filename, progress.as_ref(), &stats, &skipped, &error)?;
+            sink.extend(&args, kept)?;
+            if let Some(bar) = progress {
+                bar.finish_and_clear();
+            }
+            if error.borrow().is_some() {
+                break;
+            }
+        }
+    }
+    if let Some(err) = error.into_inner() {
+        return Err(err.into());
+    }
+
+    sink.finish(&args)?;
+    // `--validate` prints nothing for valid input, and its own summary below otherwise.
+    if !args.validate {
+        eprintln!("{}", stats.borrow());
+    }
+    if args.error_report {
+        eprint!("{}", skipped.borrow());
+    }
+    // Unreadable or undecodable input has already failed above with exit code 1; invalid
+    // lines get their own code so scripts can tell the two apart.
+    if args.validate && stats.borrow().skipped() > 0 {
+        eprintln!("{}", stats.borrow());
+        eprint!("{}", skipped.borrow());
+        return Ok(ExitCode::from(2));
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMBINED: &str = "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"GET /api HTTP/1.1\" 200 567 \
+                            \"https://example.com/\" \"curl/8.5.0\"\n";
+
+    #[test]
+    fn test_resolve_auto_through_decompress() {
+        let input = COMBINED.repeat(5).into_bytes();
+        let mut reader = decompress(io::Cursor::new(input), None).unwrap();
+        assert_eq!(InputFormat::Auto.resolve(&mut reader).unwrap(), LogFormat::Combined);
+        // Resolving only peeks, so every line is still there to parse.
+        assert_eq!(reader.lines().count(), 5);
+
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(COMBINED.repeat(5).as_bytes()).unwrap();
+        let gzipped = encoder.finish().unwrap();
+        let mut reader = decompress(io::Cursor::new(gzipped), None).unwrap();
+        assert_eq!(InputFormat::Auto.resolve(&mut reader).unwrap(), LogFormat::Combined);
+    }
+}
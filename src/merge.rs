@@ -0,0 +1,113 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+
+use crate::parser::LogEntry;
+
+// Orders the heap by timestamp, then by stream so ties come out in input order.
+struct Head {
+    stream: usize,
+    entry: LogEntry,
+}
+
+impl PartialEq for Head {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Head {}
+
+impl PartialOrd for Head {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Head {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.entry.timestamp, self.stream).cmp(&(other.entry.timestamp, other.stream))
+    }
+}
+
+/// Interleaves several time-ordered streams into one, see `merge_by_timestamp`.
+pub struct MergeByTimestamp<I: Iterator<Item = LogEntry>> {
+    streams: Vec<I>,
+    heads: BinaryHeap<Reverse<Head>>,
+}
+
+impl<I: Iterator<Item = LogEntry>> MergeByTimestamp<I> {
+    fn pull(&mut self, stream: usize) {
+        if let Some(entry) = self.streams[stream].next() {
+            self.heads.push(Reverse(Head { stream, entry }));
+        }
+    }
+}
+
+impl<I: Iterator<Item = LogEntry>> Iterator for MergeByTimestamp<I> {
+    type Item = LogEntry;
+
+    fn next(&mut self) -> Option<LogEntry> {
+        let Reverse(head) = self.heads.pop()?;
+        self.pull(head.stream);
+        Some(head.entry)
+    }
+}
+
+/// A k-way merge of streams that are each already sorted by timestamp, such as the access
+/// logs of several servers. Entries with equal timestamps come out in stream order.
+///
+/// Each stream must be sorted on its own; the merge does not check this, and an
+/// out-of-order entry is simply emitted when it reaches the front of its stream.
+pub fn merge_by_timestamp<I: Iterator<Item = LogEntry>>(streams: Vec<I>) -> MergeByTimestamp<I> {
+    let mut merged = MergeByTimestamp {
+        heads: BinaryHeap::with_capacity(streams.len()),
+        streams,
+    };
+    for stream in 0..merged.streams.len() {
+        merged.pull(stream);
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{FixedOffset, TimeZone, Utc};
+
+    fn entry(second: u32, path: &str) -> LogEntry {
+        LogEntry::builder()
+            .timestamp(Utc.with_ymd_and_hms(2024, 1, 15, 10, 24, second).unwrap())
+            .path(path)
+            .build()
+    }
+
+    #[test]
+    fn test_merge_by_timestamp() {
+        let streams = vec![
+            vec![entry(1, "/a1"), entry(4, "/a4"), entry(9, "/a9")].into_iter(),
+            vec![].into_iter(),
+            vec![entry(2, "/b2"), entry(4, "/b4")].into_iter(),
+            vec![entry(0, "/c0"), entry(10, "/c10")].into_iter(),
+        ];
+
+        let paths = merge_by_timestamp(streams).map(|entry| entry.path).collect::<Vec<_>>();
+        assert_eq!(paths, ["/c0", "/a1", "/b2", "/a4", "/b4", "/a9", "/c10"]);
+
+        let none: Vec<std::vec::IntoIter<LogEntry>> = Vec::new();
+        assert_eq!(merge_by_timestamp(none).count(), 0);
+    }
+
+    #[test]
+    fn test_merge_compares_instants_across_offsets() {
+        let offset = FixedOffset::east_opt(2 * 3600).unwrap();
+        let later_local = entry(5, "/utc");
+        let earlier_instant = LogEntry::builder()
+            .timestamp(offset.with_ymd_and_hms(2024, 1, 15, 12, 24, 3).unwrap())
+            .path("/plus2")
+            .build();
+
+        let streams = vec![vec![later_local].into_iter(), vec![earlier_instant].into_iter()];
+        let paths = merge_by_timestamp(streams).map(|entry| entry.path).collect::<Vec<_>>();
+        assert_eq!(paths, ["/plus2", "/utc"]);
+    }
+}
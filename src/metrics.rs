@@ -0,0 +1,96 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::parser::LogEntry;
+
+/// Request counts keyed by status class label (`2xx`) and method.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RequestCounts {
+    counts: BTreeMap<(&'static str, String), u64>,
+}
+
+impl RequestCounts {
+    pub fn new() -> Self {
+        RequestCounts::default()
+    }
+
+    pub fn add(&mut self, entry: &LogEntry) {
+        let key = (entry.status_class().label(), entry.method.clone());
+        *self.counts.entry(key).or_default() += 1;
+    }
+
+    pub fn count(&self, status_class: &str, method: &str) -> u64 {
+        self.counts
+            .iter()
+            .find(|((class, m), _)| *class == status_class && m == method)
+            .map_or(0, |(_, count)| *count)
+    }
+
+    /// `(status class, method, count)`, ordered by class then method.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str, u64)> {
+        self.counts
+            .iter()
+            .map(|((class, method), count)| (*class, method.as_str(), *count))
+    }
+}
+
+impl<'a> FromIterator<&'a LogEntry> for RequestCounts {
+    fn from_iter<I: IntoIterator<Item = &'a LogEntry>>(entries: I) -> Self {
+        let mut counts = RequestCounts::new();
+        for entry in entries {
+            counts.add(entry);
+        }
+        counts
+    }
+}
+
+/// Renders `RequestCounts` in the Prometheus text exposition format.
+pub struct Prometheus<'a>(pub &'a RequestCounts);
+
+// Label values escape backslashes, quotes and newlines.
+fn escape_label(value: &str) -> String {
+    value.replace('\\', r"\\").replace('"', "\\\"").replace('\n', r"\n")
+}
+
+// # TYPE http_requests_total counter
+// http_requests_total{status="2xx",method="GET"} 1234
+impl fmt::Display for Prometheus<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "# HELP http_requests_total Requests parsed from the access log.")?;
+        writeln!(f, "# TYPE http_requests_total counter")?;
+        for (class, method, count) in self.0.iter() {
+            writeln!(
+                f,
+                "http_requests_total{{status=\"{}\",method=\"{}\"}} {}",
+                class,
+                escape_label(method),
+                count
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prometheus_output() {
+        let entries = [("GET", 200), ("GET", 204), ("POST", 201), ("GET", 404), ("GET", 200), ("B\"AD", 999)]
+            .map(|(method, status)| LogEntry::builder().method(method).status(status).build());
+        let counts = entries.iter().collect::<RequestCounts>();
+
+        assert_eq!(counts.count("2xx", "GET"), 3);
+        assert_eq!(counts.count("5xx", "GET"), 0);
+        assert_eq!(
+            Prometheus(&counts).to_string(),
+            "# HELP http_requests_total Requests parsed from the access log.\n\
+             # TYPE http_requests_total counter\n\
+             http_requests_total{status=\"2xx\",method=\"GET\"} 3\n\
+             http_requests_total{status=\"2xx\",method=\"POST\"} 1\n\
+             http_requests_total{status=\"4xx\",method=\"GET\"} 1\n\
+             http_requests_total{status=\"unknown\",method=\"B\\\"AD\"} 1\n"
+        );
+    }
+}
@@ -0,0 +1,36 @@
+use rayon::prelude::*;
+
+use crate::parser::{LogEntry, ParseError, parse_common_log};
+
+/// Results are returned in the same order as `lines`.
+pub fn parse_lines_parallel(lines: &[String]) -> Vec<Result<LogEntry, ParseError>> {
+    lines.par_iter().map(|line| parse_common_log(line)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parallel_preserves_order() {
+        let lines = (0..1000)
+            .map(|i| match i % 7 {
+                0 => format!("garbage line {i}"),
+                _ => format!("10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"GET /item/{i} HTTP/1.1\" 200 {i}"),
+            })
+            .collect::<Vec<_>>();
+
+        let parallel = parse_lines_parallel(&lines);
+        assert_eq!(parallel.len(), lines.len());
+        for (i, (line, result)) in lines.iter().zip(parallel).enumerate() {
+            match parse_common_log(line) {
+                Ok(expected) => {
+                    let result = result.unwrap();
+                    assert_eq!(result.path, expected.path);
+                    assert_eq!(result.size, i as u64);
+                }
+                Err(expected) => assert_eq!(result.unwrap_err(), expected),
+            }
+        }
+    }
+}
@@ -1,81 +1,919 @@
-use anyhow::Result;
-use chrono::{DateTime, TimeZone, Utc};
-use regex::Regex;
+use alloc::borrow::Cow;
+use alloc::collections::BTreeMap;
+use core::cmp::{Ordering, Reverse};
+use core::convert::Infallible;
+use core::fmt;
+use core::net::{AddrParseError, IpAddr, Ipv4Addr, Ipv6Addr};
+use core::num::ParseIntError;
+use core::str::FromStr;
+use core::time::Duration;
+use std::sync::LazyLock;
+
+use chrono::{DateTime, FixedOffset, NaiveDateTime};
+use percent_encoding::percent_decode_str;
+use regex::{Captures, Regex};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-#[derive(Debug, Clone, Default)]
+use crate::format::FormatError;
+
+/// Equality and hashing follow `DateTime`, so timestamps naming the same instant in
+/// different offsets compare equal.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct LogEntry {
-    pub ip: String,
-    pub timestamp: DateTime<Utc>,
+    /// Server name from the leading `%v` column of vhost formats.
+    pub vhost: Option<String>,
+    pub ip: IpAddr,
+    pub identity: Option<String>,
+    pub user: Option<String>,
+    pub timestamp: DateTime<FixedOffset>,
+    /// The timestamp exactly as logged, kept when `ParseOptions::keep_raw_timestamp` is set.
+    pub raw_timestamp: Option<String>,
     pub method: String,
+    /// Host from an absolute-form request target (`GET http://example.com/ HTTP/1.1`), as
+    /// sent by clients of forward proxies.
+    pub host: Option<String>,
     pub path: String,
+    /// Everything after the first `?` in the request target; a bare trailing `?` gives `Some("")`.
+    pub query: Option<String>,
+    pub protocol: String,
     pub status: u16,
     pub size: u64,
+    pub referrer: Option<String>,
+    pub user_agent: Option<String>,
+    pub country: Option<String>,
+    /// Time taken to serve the request, when the format logs it (`%D`, `%T`).
+    pub duration: Option<Duration>,
+    /// Extra columns captured by custom formats (`%{Name}i`, `%{Name}o`, `%{Name}C`, `%{Name}x`),
+    /// keyed by header, cookie or variable name. A `BTreeMap` keeps the entry hashable.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "BTreeMap::is_empty"))]
+    pub fields: BTreeMap<String, String>,
+}
+
+impl Default for LogEntry {
+    fn default() -> Self {
+        LogEntry {
+            vhost: None,
+            ip: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            identity: None,
+            user: None,
+            timestamp: DateTime::default(),
+            raw_timestamp: None,
+            method: String::new(),
+            host: None,
+            path: String::new(),
+            query: None,
+            protocol: String::new(),
+            status: 0,
+            size: 0,
+            referrer: None,
+            user_agent: None,
+            country: None,
+            duration: None,
+            fields: BTreeMap::new(),
+        }
+    }
+}
+
+impl LogEntry {
+    pub fn builder() -> LogEntryBuilder {
+        LogEntryBuilder::default()
+    }
+}
+
+/// Entries order by time first, comparing instants like `Eq` does, so `entries.sort()` is
+/// chronological; the remaining fields, in declaration order, only break ties.
+impl Ord for LogEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.timestamp
+            .cmp(&other.timestamp)
+            .then_with(|| self.vhost.cmp(&other.vhost))
+            .then_with(|| self.ip.cmp(&other.ip))
+            .then_with(|| self.identity.cmp(&other.identity))
+            .then_with(|| self.user.cmp(&other.user))
+            .then_with(|| self.raw_timestamp.cmp(&other.raw_timestamp))
+            .then_with(|| self.method.cmp(&other.method))
+            .then_with(|| self.host.cmp(&other.host))
+            .then_with(|| self.path.cmp(&other.path))
+            .then_with(|| self.query.cmp(&other.query))
+            .then_with(|| self.protocol.cmp(&other.protocol))
+            .then_with(|| self.status.cmp(&other.status))
+            .then_with(|| self.size.cmp(&other.size))
+            .then_with(|| self.referrer.cmp(&other.referrer))
+            .then_with(|| self.user_agent.cmp(&other.user_agent))
+            .then_with(|| self.country.cmp(&other.country))
+            .then_with(|| self.duration.cmp(&other.duration))
+            .then_with(|| self.fields.cmp(&other.fields))
+    }
+}
+
+impl PartialOrd for LogEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Formats the entry as a common log line, the inverse of `parse_common_log`.
+impl fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // The scheme of an absolute-form target isn't kept; proxies see plain `http` there.
+        let mut target = match &self.host {
+            Some(host) => format!("http://{}{}", host, self.path),
+            None => self.path.clone(),
+        };
+        if let Some(query) = &self.query {
+            target.push('?');
+            target.push_str(query);
+        }
+        let request = match (self.method.as_str(), target.as_str(), self.protocol.as_str()) {
+            ("", "", "") => "-".to_string(),
+            _ => format!("{} {} {}", self.method, target, self.protocol),
+        };
+
+        write!(
+            f,
+            "{} {} {} [{}] \"{}\" {} {}",
+            self.ip,
+            self.identity.as_deref().unwrap_or("-"),
+            self.user.as_deref().unwrap_or("-"),
+            self.timestamp.format(CLF_TIMESTAMP_FORMAT),
+            escape_quoted(&request),
+            self.status,
+            self.size
+        )
+    }
+}
+
+/// Fluent constructor for `LogEntry`; unset fields keep their `Default` values.
+#[derive(Debug, Clone, Default)]
+pub struct LogEntryBuilder {
+    entry: LogEntry,
+}
+
+impl LogEntryBuilder {
+    pub fn vhost(mut self, vhost: impl Into<String>) -> Self {
+        self.entry.vhost = Some(vhost.into());
+        self
+    }
+
+    pub fn ip(mut self, ip: impl Into<IpAddr>) -> Self {
+        self.entry.ip = ip.into();
+        self
+    }
+
+    pub fn identity(mut self, identity: impl Into<String>) -> Self {
+        self.entry.identity = Some(identity.into());
+        self
+    }
+
+    pub fn user(mut self, user: impl Into<String>) -> Self {
+        self.entry.user = Some(user.into());
+        self
+    }
+
+    pub fn timestamp(mut self, timestamp: impl Into<DateTime<FixedOffset>>) -> Self {
+        self.entry.timestamp = timestamp.into();
+        self
+    }
+
+    pub fn raw_timestamp(mut self, raw_timestamp: impl Into<String>) -> Self {
+        self.entry.raw_timestamp = Some(raw_timestamp.into());
+        self
+    }
+
+    pub fn method(mut self, method: impl Into<String>) -> Self {
+        self.entry.method = method.into();
+        self
+    }
+
+    pub fn host(mut self, host: impl Into<String>) -> Self {
+        self.entry.host = Some(host.into());
+        self
+    }
+
+    pub fn path(mut self, path: impl Into<String>) -> Self {
+        self.entry.path = path.into();
+        self
+    }
+
+    pub fn query(mut self, query: impl Into<String>) -> Self {
+        self.entry.query = Some(query.into());
+        self
+    }
+
+    pub fn protocol(mut self, protocol: impl Into<String>) -> Self {
+        self.entry.protocol = protocol.into();
+        self
+    }
+
+    pub fn status(mut self, status: u16) -> Self {
+        self.entry.status = status;
+        self
+    }
+
+    pub fn size(mut self, size: u64) -> Self {
+        self.entry.size = size;
+        self
+    }
+
+    pub fn referrer(mut self, referrer: impl Into<String>) -> Self {
+        self.entry.referrer = Some(referrer.into());
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.entry.user_agent = Some(user_agent.into());
+        self
+    }
+
+    pub fn country(mut self, country: impl Into<String>) -> Self {
+        self.entry.country = Some(country.into());
+        self
+    }
+
+    pub fn duration(mut self, duration: Duration) -> Self {
+        self.entry.duration = Some(duration);
+        self
+    }
+
+    pub fn field(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.entry.fields.insert(name.into(), value.into());
+        self
+    }
+
+    pub fn build(self) -> LogEntry {
+        self.entry
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StatusClass {
+    Informational,
+    Success,
+    Redirection,
+    ClientError,
+    ServerError,
+    Unknown,
+}
+
+impl StatusClass {
+    /// `2xx`-style label; `unknown` for codes outside 100-599.
+    pub fn label(self) -> &'static str {
+        match self {
+            StatusClass::Informational => "1xx",
+            StatusClass::Success => "2xx",
+            StatusClass::Redirection => "3xx",
+            StatusClass::ClientError => "4xx",
+            StatusClass::ServerError => "5xx",
+            StatusClass::Unknown => "unknown",
+        }
+    }
 }
 
-#[derive(Error, Debug, PartialEq)]
+/// Unit prefixes for `format_size`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SizeUnits {
+    /// Powers of 1024: KiB, MiB, GiB, ...
+    #[default]
+    Binary,
+    /// Powers of 1000: kB, MB, GB, ...
+    Decimal,
+}
+
+/// `bytes` in the largest unit that keeps the value at least 1, with one decimal past
+/// bytes: `0 B`, `512 B`, `1.2 KiB`, `3.4 MiB`.
+pub fn format_size(bytes: u64, units: SizeUnits) -> String {
+    let (base, names) = match units {
+        SizeUnits::Binary => (1024.0, ["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"]),
+        SizeUnits::Decimal => (1000.0, ["B", "kB", "MB", "GB", "TB", "PB", "EB"]),
+    };
+    let mut value = bytes as f64;
+    if value < base {
+        return format!("{bytes} B");
+    }
+    let mut unit = 0;
+    // Compares the rounded value, so 1023.96 KiB shows as `1.0 MiB` rather than `1024.0 KiB`.
+    while unit + 1 < names.len() && (value * 10.0).round() / 10.0 >= base {
+        value /= base;
+        unit += 1;
+    }
+    format!("{value:.1} {}", names[unit])
+}
+
+/// Parses a common log line, like `parse_common_log`.
+impl FromStr for LogEntry {
+    type Err = ParseError;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        parse_common_log(line)
+    }
+}
+
+impl LogEntry {
+    /// The path with percent-escapes decoded; malformed escapes are kept as-is and
+    /// invalid UTF-8 is replaced with U+FFFD.
+    pub fn decoded_path(&self) -> Cow<'_, str> {
+        percent_decode_str(&self.path).decode_utf8_lossy()
+    }
+
+    /// The non-empty `/`-separated segments of the path, so `/api/users/` gives
+    /// `["api", "users"]`. The query string is never part of `path`.
+    pub fn path_segments(&self) -> Vec<&str> {
+        self.path.split('/').filter(|segment| !segment.is_empty()).collect()
+    }
+
+    /// The host of the referrer URL, e.g. `example.com` for `https://example.com/page`; `None`
+    /// without a referrer or when it isn't a URL with a host.
+    pub fn referrer_host(&self) -> Option<String> {
+        let referrer = url::Url::parse(self.referrer.as_deref()?).ok()?;
+        referrer.host_str().map(str::to_string)
+    }
+
+    /// The response size in binary units, e.g. `1.2 KiB`; see `format_size` for decimal ones.
+    pub fn content_length_human(&self) -> String {
+        format_size(self.size, SizeUnits::Binary)
+    }
+
+    /// The client address with its last IPv4 octet or last 80 IPv6 bits zeroed, as Google
+    /// Analytics anonymizes IPs.
+    pub fn anonymized_ip(&self) -> IpAddr {
+        match self.ip {
+            IpAddr::V4(ip) => IpAddr::V4(Ipv4Addr::from(ip.to_bits() & !0xff)),
+            IpAddr::V6(ip) => IpAddr::V6(Ipv6Addr::from(ip.to_bits() & !((1 << 80) - 1))),
+        }
+    }
+
+    pub fn status_class(&self) -> StatusClass {
+        match self.status {
+            100..=199 => StatusClass::Informational,
+            200..=299 => StatusClass::Success,
+            300..=399 => StatusClass::Redirection,
+            400..=499 => StatusClass::ClientError,
+            500..=599 => StatusClass::ServerError,
+            _ => StatusClass::Unknown,
+        }
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.status_class() == StatusClass::Success
+    }
+
+    pub fn is_redirect(&self) -> bool {
+        self.status_class() == StatusClass::Redirection
+    }
+
+    pub fn is_client_error(&self) -> bool {
+        self.status_class() == StatusClass::ClientError
+    }
+
+    pub fn is_server_error(&self) -> bool {
+        self.status_class() == StatusClass::ServerError
+    }
+
+    /// Either a client (4xx) or server (5xx) error.
+    pub fn is_error(&self) -> bool {
+        self.is_client_error() || self.is_server_error()
+    }
+}
+
+#[derive(Error, Debug)]
 pub enum ParseError {
-    #[error("Invalid log format")]
+    #[error("invalid log format")]
     InvalidFormat,
 
-    #[error("Invalid timestamp ")]
-    InvalidTimestamp,
+    /// The line ends early, e.g. a truncated write, but the fields present have the right shape.
+    #[error("expected {expected} fields, found {found}")]
+    TooFewFields { expected: usize, found: usize },
+
+    #[error("unexpected trailing data: {0:?}")]
+    UnexpectedTrailing(String),
+
+    #[error("line longer than {limit} bytes")]
+    LineTooLong { limit: usize },
+
+    #[error("invalid IP address: {value:?}")]
+    InvalidIp { value: String, source: AddrParseError },
+
+    /// `source` is `None` for epoch timestamps, which are not parsed by chrono.
+    #[error("invalid timestamp: {value:?}")]
+    InvalidTimestamp {
+        value: String,
+        source: Option<chrono::ParseError>,
+    },
+
+    #[error("invalid request line: {0:?}")]
+    InvalidRequest(String),
+
+    #[error("invalid HTTP method: {0:?}")]
+    InvalidMethod(String),
+
+    #[error("invalid status code: {0:?}")]
+    InvalidStatus(String),
+
+    #[error("invalid size: {value:?}")]
+    InvalidSize { value: String, source: ParseIntError },
+
+    #[error("invalid duration: {value:?}")]
+    InvalidDuration { value: String, source: ParseIntError },
+
+    #[error(transparent)]
+    InvalidTemplate(#[from] FormatError),
+
+    /// Reading the input failed; only produced by the std readers.
+    #[cfg(feature = "std")]
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+// `io::Error` isn't `PartialEq`, so I/O errors compare by kind.
+impl PartialEq for ParseError {
+    fn eq(&self, other: &Self) -> bool {
+        use ParseError::*;
+        match (self, other) {
+            (InvalidFormat, InvalidFormat) => true,
+            (TooFewFields { expected: a, found: b }, TooFewFields { expected: c, found: d }) => a == c && b == d,
+            (UnexpectedTrailing(a), UnexpectedTrailing(b)) => a == b,
+            (LineTooLong { limit: a }, LineTooLong { limit: b }) => a == b,
+            (InvalidIp { value: a, source: b }, InvalidIp { value: c, source: d }) => a == c && b == d,
+            (InvalidTimestamp { value: a, source: b }, InvalidTimestamp { value: c, source: d }) => a == c && b == d,
+            (InvalidRequest(a), InvalidRequest(b)) => a == b,
+            (InvalidMethod(a), InvalidMethod(b)) => a == b,
+            (InvalidStatus(a), InvalidStatus(b)) => a == b,
+            (InvalidSize { value: a, source: b }, InvalidSize { value: c, source: d }) => a == c && b == d,
+            (InvalidDuration { value: a, source: b }, InvalidDuration { value: c, source: d }) => a == c && b == d,
+            (InvalidTemplate(a), InvalidTemplate(b)) => a == b,
+            #[cfg(feature = "std")]
+            (Io(a), Io(b)) => a.kind() == b.kind(),
+            _ => false,
+        }
+    }
+}
+
+impl ParseError {
+    /// A stable snake_case name for the variant, e.g. `invalid_timestamp`.
+    pub fn kind_name(&self) -> &'static str {
+        match self {
+            ParseError::InvalidFormat => "invalid_format",
+            ParseError::TooFewFields { .. } => "too_few_fields",
+            ParseError::UnexpectedTrailing(_) => "unexpected_trailing",
+            ParseError::LineTooLong { .. } => "line_too_long",
+            ParseError::InvalidIp { .. } => "invalid_ip",
+            ParseError::InvalidTimestamp { .. } => "invalid_timestamp",
+            ParseError::InvalidRequest(_) => "invalid_request",
+            ParseError::InvalidMethod(_) => "invalid_method",
+            ParseError::InvalidStatus(_) => "invalid_status",
+            ParseError::InvalidSize { .. } => "invalid_size",
+            ParseError::InvalidDuration { .. } => "invalid_duration",
+            ParseError::InvalidTemplate(_) => "invalid_template",
+            #[cfg(feature = "std")]
+            ParseError::Io(_) => "io",
+        }
+    }
+}
+
+pub const HTTP_METHODS: [&str; 9] = ["GET", "POST", "PUT", "DELETE", "HEAD", "OPTIONS", "PATCH", "TRACE", "CONNECT"];
+
+const CLF_TIMESTAMP_FORMAT: &str = "%d/%b/%Y:%H:%M:%S %z";
+
+/// How the bracketed timestamp field is read.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum TimestampFormat {
+    /// `01/Jan/2024:12:00:00 +0000`
+    #[default]
+    Clf,
+    /// A chrono strftime pattern, e.g. `%+` for ISO 8601; patterns without an offset are read as UTC.
+    Pattern(String),
+    /// Integer seconds since the Unix epoch.
+    Epoch,
+    /// Integer milliseconds since the Unix epoch.
+    EpochMillis,
+}
+
+impl TimestampFormat {
+    // The error is chrono's, if chrono did the parsing.
+    fn parse(&self, timestamp: &str) -> Result<DateTime<FixedOffset>, Option<chrono::ParseError>> {
+        let utc = match self {
+            TimestampFormat::Clf => return DateTime::parse_from_str(timestamp, CLF_TIMESTAMP_FORMAT).map_err(Some),
+            TimestampFormat::Pattern(pattern) => {
+                if let Ok(timestamp) = DateTime::parse_from_str(timestamp, pattern) {
+                    return Ok(timestamp);
+                }
+                NaiveDateTime::parse_from_str(timestamp, pattern).map_err(Some)?.and_utc()
+            }
+            TimestampFormat::Epoch => timestamp
+                .parse()
+                .ok()
+                .and_then(|seconds| DateTime::from_timestamp(seconds, 0))
+                .ok_or(None)?,
+            TimestampFormat::EpochMillis => timestamp
+                .parse()
+                .ok()
+                .and_then(DateTime::from_timestamp_millis)
+                .ok_or(None)?,
+        };
+        Ok(utc.fixed_offset())
+    }
+}
+
+/// `epoch` and `epoch_ms` select the integer formats; anything else is a strftime pattern.
+impl FromStr for TimestampFormat {
+    type Err = Infallible;
 
-    #[error("Invalid status code")]
-    InvalidStatus,
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "epoch" => TimestampFormat::Epoch,
+            "epoch_ms" => TimestampFormat::EpochMillis,
+            CLF_TIMESTAMP_FORMAT => TimestampFormat::Clf,
+            pattern => TimestampFormat::Pattern(pattern.to_string()),
+        })
+    }
+}
 
-    #[error("Invalid size")]
-    InvalidSize,
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    /// Reject methods outside `HTTP_METHODS` instead of storing them verbatim.
+    pub strict_methods: bool,
+    /// Uppercase methods, so `get` and `Get` aggregate with `GET`. Applied before the
+    /// `strict_methods` check.
+    pub uppercase_methods: bool,
+    pub timestamp_format: TimestampFormat,
+    /// Report blank and `#` comment lines as errors instead of skipping them.
+    pub report_blank_lines: bool,
+    /// Store the timestamp text as captured in `LogEntry::raw_timestamp`.
+    pub keep_raw_timestamp: bool,
+    /// Month abbreviations to accept in place of English `Jan`..`Dec`, January first, for
+    /// servers logging in another locale (e.g. German `Mär`, `Okt`, `Dez`).
+    pub month_names: Option<[String; 12]>,
 }
 
+const ENGLISH_MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+// chrono only parses English month names, so a localized one is swapped for its English
+// abbreviation before parsing. Longer names are tried first so `juil.` wins over `jui`.
+fn with_english_month(timestamp: &str, month_names: &[String; 12]) -> Option<String> {
+    let mut names = month_names.iter().zip(ENGLISH_MONTHS).collect::<Vec<_>>();
+    names.sort_by_key(|(name, _)| Reverse(name.len()));
+    names
+        .into_iter()
+        .find(|(name, _)| !name.is_empty() && timestamp.contains(name.as_str()))
+        .map(|(name, english)| timestamp.replacen(name.as_str(), english, 1))
+}
+
+// 127.0.0.1 - - [01/Jan/2024:12:00:00 +0000] "GET /api HTTP/1.1" 200 1234
+static COMMON_LOG_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"^(?<ip>[[:xdigit:]:.]+) (?<identity>\S+) (?<user>\S+) \[(?<timestamp>.+)\] "(?<request>(?:[^"\\]|\\.)*)" (?<status>\S+) (?<size>\S+)$"#).unwrap()
+});
+
+// 127.0.0.1 - - [01/Jan/2024:12:00:00 +0000] "GET /api HTTP/1.1" 200 1234 "http://example.com/" "Mozilla/5.0"
+static COMBINED_LOG_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"^(?<ip>[[:xdigit:]:.]+) (?<identity>\S+) (?<user>\S+) \[(?<timestamp>.+)\] "(?<request>(?:[^"\\]|\\.)*)" (?<status>\S+) (?<size>\S+) "(?<referrer>(?:[^"\\]|\\.)*)" "(?<user_agent>(?:[^"\\]|\\.)*)"$"#).unwrap()
+});
+
+// www.example.com 127.0.0.1 - - [01/Jan/2024:12:00:00 +0000] "GET /api HTTP/1.1" 200 1234 "-" "Mozilla/5.0"
+static VHOST_COMBINED_LOG_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"^(?<vhost>\S+) (?<ip>[[:xdigit:]:.]+) (?<identity>\S+) (?<user>\S+) \[(?<timestamp>.+)\] "(?<request>(?:[^"\\]|\\.)*)" (?<status>\S+) (?<size>\S+) "(?<referrer>(?:[^"\\]|\\.)*)" "(?<user_agent>(?:[^"\\]|\\.)*)"$"#).unwrap()
+});
+
 pub fn parse_common_log(line: &str) -> Result<LogEntry, ParseError> {
-    // 127.0.0.1 - - [01/Jan/2024:12:00:00 +0000] "GET /api HTTP/1.1" 200 1234
-    let pattern = r#"(?<ip>[[:digit:]]{1,3}\.[[:digit:]]{1,3}\.[[:digit:]]{1,3}\.[[:digit:]]{1,3}) - - \[(?<timestamp>.+)\] "(?<method>.+) (?<path>/.+) .+" (?<status>[[:digit:]]{3}) (?<size>.+)"#;
-    let regex = Regex::new(pattern).unwrap();
-    let caps = regex.captures(line).ok_or(ParseError::InvalidFormat)?;
-
-    let date_format = "%d/%b/%Y:%H:%M:%S %z";
-    let timestamp: DateTime<Utc> = DateTime::parse_from_str(&caps["timestamp"], date_format)
-        .map_err(|_| ParseError::InvalidTimestamp)?
-        .to_utc();
-
-    let ip = caps["ip"].to_string();
-    let method = caps["method"].to_string();
-    let path = caps["path"].to_string();
-    let status = caps["status"]
-        .parse::<u16>()
-        .map_err(|_| ParseError::InvalidStatus)?;
-    let size = caps["size"]
-        .parse::<u64>()
-        .map_err(|_| ParseError::InvalidSize)?;
-
-    let entry = LogEntry {
-        ip,
-        timestamp,
-        method,
-        path,
-        status,
-        size,
+    parse_common_log_with(line, &ParseOptions::default())
+}
+
+/// Trailing whitespace, including a `\r` left by CRLF line endings, is ignored.
+pub fn parse_common_log_with(line: &str, options: &ParseOptions) -> Result<LogEntry, ParseError> {
+    let line = line.trim_end();
+    let caps = COMMON_LOG_REGEX
+        .captures(line)
+        .ok_or_else(|| diagnose_fields(line, COMMON_FIELDS))?;
+
+    entry_from_captures(&caps, options)
+}
+
+pub fn parse_combined_log(line: &str) -> Result<LogEntry, ParseError> {
+    parse_combined_log_with(line, &ParseOptions::default())
+}
+
+pub fn parse_combined_log_with(line: &str, options: &ParseOptions) -> Result<LogEntry, ParseError> {
+    let line = line.trim_end();
+    let caps = COMBINED_LOG_REGEX
+        .captures(line)
+        .ok_or_else(|| diagnose_fields(line, COMBINED_FIELDS))?;
+
+    entry_from_captures(&caps, options)
+}
+
+pub fn parse_vhost_combined_log(line: &str) -> Result<LogEntry, ParseError> {
+    parse_vhost_combined_log_with(line, &ParseOptions::default())
+}
+
+/// Combined format behind a server-name column, Apache's `%v %h %l %u %t "%r" %>s %b ...`.
+pub fn parse_vhost_combined_log_with(line: &str, options: &ParseOptions) -> Result<LogEntry, ParseError> {
+    let line = line.trim_end();
+    let caps = VHOST_COMBINED_LOG_REGEX.captures(line).ok_or_else(|| {
+        // Any server name is plausible, so only the columns after it are diagnosed.
+        let Some((_, rest)) = line.split_once(' ') else {
+            return ParseError::InvalidFormat;
+        };
+        match diagnose_fields(rest, COMBINED_FIELDS) {
+            ParseError::TooFewFields { expected, found } => ParseError::TooFewFields {
+                expected: expected + 1,
+                found: found + 1,
+            },
+            err => err,
+        }
+    })?;
+
+    entry_from_captures(&caps, options)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FieldShape {
+    Bare,
+    Bracketed,
+    Quoted,
+}
+
+const COMMON_FIELDS: &[FieldShape] = &[
+    FieldShape::Bare,
+    FieldShape::Bare,
+    FieldShape::Bare,
+    FieldShape::Bracketed,
+    FieldShape::Quoted,
+    FieldShape::Bare,
+    FieldShape::Bare,
+];
+
+const COMBINED_FIELDS: &[FieldShape] = &[
+    FieldShape::Bare,
+    FieldShape::Bare,
+    FieldShape::Bare,
+    FieldShape::Bracketed,
+    FieldShape::Quoted,
+    FieldShape::Bare,
+    FieldShape::Bare,
+    FieldShape::Quoted,
+    FieldShape::Quoted,
+];
+
+enum Field<'a> {
+    /// The remainder of the line after the field.
+    Complete(&'a str),
+    /// An opening `[` or `"` that is never closed.
+    Truncated,
+    Mismatch,
+}
+
+fn next_field(rest: &str, shape: FieldShape) -> Field<'_> {
+    let close = match (shape, rest.chars().next()) {
+        (FieldShape::Bare, Some('[' | '"')) => return Field::Mismatch,
+        (FieldShape::Bare, _) => return Field::Complete(&rest[rest.find(' ').unwrap_or(rest.len())..]),
+        (FieldShape::Bracketed, Some('[')) => rest.find(']'),
+        (FieldShape::Quoted, Some('"')) => {
+            let mut escaped = false;
+            rest.char_indices().skip(1).find_map(|(i, c)| match c {
+                '"' if !escaped => Some(i),
+                c => {
+                    escaped = c == '\\' && !escaped;
+                    None
+                }
+            })
+        }
+        _ => return Field::Mismatch,
+    };
+    match close {
+        Some(i) => Field::Complete(&rest[i + 1..]),
+        None => Field::Truncated,
+    }
+}
+
+// Called once the line regex has failed, to tell a truncated line or one with extra
+// columns apart from a line of the wrong shape altogether.
+fn diagnose_fields(line: &str, shapes: &[FieldShape]) -> ParseError {
+    let too_few = |found| ParseError::TooFewFields {
+        expected: shapes.len(),
+        found,
     };
+
+    // Without a plausible address up front this is not a log line at all.
+    let first = line.split(' ').next().unwrap_or_default();
+    if first.is_empty() || !first.chars().all(|c| c.is_ascii_hexdigit() || c == ':' || c == '.') {
+        return ParseError::InvalidFormat;
+    }
+
+    let mut rest = line;
+    for (found, shape) in shapes.iter().enumerate() {
+        if found > 0 {
+            match rest.strip_prefix(' ') {
+                Some(next) => rest = next,
+                None if rest.is_empty() => return too_few(found),
+                None => return ParseError::InvalidFormat,
+            }
+        }
+        rest = match next_field(rest, *shape) {
+            Field::Complete(next) => next,
+            Field::Truncated => return too_few(found),
+            Field::Mismatch => return ParseError::InvalidFormat,
+        };
+    }
+
+    match rest.strip_prefix(' ') {
+        Some(trailing) if !trailing.is_empty() => ParseError::UnexpectedTrailing(trailing.to_string()),
+        _ => ParseError::InvalidFormat,
+    }
+}
+
+/// Parses each line as a common log entry, returning the entries that parsed and the
+/// zero-based index of every line that failed alongside its error.
+pub fn parse_many<'a>(lines: impl IntoIterator<Item = &'a str>) -> (Vec<LogEntry>, Vec<(usize, ParseError)>) {
+    let mut entries = Vec::new();
+    let mut errors = Vec::new();
+    for (index, line) in lines.into_iter().enumerate() {
+        match parse_common_log(line) {
+            Ok(entry) => entries.push(entry),
+            Err(error) => errors.push((index, error)),
+        }
+    }
+    (entries, errors)
+}
+
+// Groups missing from the pattern (e.g. a custom format without `%t`) keep their defaults.
+pub(crate) fn entry_from_captures(caps: &Captures, options: &ParseOptions) -> Result<LogEntry, ParseError> {
+    let mut entry = LogEntry::default();
+
+    if let Some(timestamp) = caps.name("timestamp") {
+        // Some formats pad the bracketed timestamp, e.g. `[ 15/Jan/2024:10:24:12 +0000 ]`.
+        let text = timestamp.as_str().trim();
+        let english = options.month_names.as_ref().and_then(|names| with_english_month(text, names));
+        entry.timestamp = options
+            .timestamp_format
+            .parse(english.as_deref().unwrap_or(text))
+            .map_err(|source| ParseError::InvalidTimestamp {
+                value: timestamp.as_str().to_string(),
+                source,
+            })?;
+        if options.keep_raw_timestamp {
+            entry.raw_timestamp = Some(timestamp.as_str().to_string());
+        }
+    }
+    // `IpAddr` parsing range-checks each octet, so `256.1.1.1` is rejected here.
+    if let Some(ip) = caps.name("ip") {
+        entry.ip = ip
+            .as_str()
+            .parse::<IpAddr>()
+            .map_err(|source| ParseError::InvalidIp {
+                value: ip.as_str().to_string(),
+                source,
+            })?;
+    }
+    entry.vhost = caps.name("vhost").and_then(|m| optional_field(m.as_str()));
+    entry.identity = caps.name("identity").and_then(|m| optional_field(m.as_str()));
+    entry.user = caps.name("user").and_then(|m| optional_field(m.as_str()));
+    // Servers log a bare "-", or nginx an empty `$request`, when the client never sent a
+    // request line, e.g. a timed-out connection; the method, path and protocol stay empty.
+    if let Some(request) = caps.name("request").filter(|m| !matches!(m.as_str(), "-" | "")) {
+        let (mut method, target, protocol) = parse_request(&unescape_quoted(request.as_str()))?;
+        if options.uppercase_methods {
+            method.make_ascii_uppercase();
+        }
+        if options.strict_methods && !HTTP_METHODS.contains(&method.as_str()) {
+            return Err(ParseError::InvalidMethod(method));
+        }
+        let (host, target) = split_absolute_target(&target);
+        let (path, query) = match target.split_once('?') {
+            Some((path, query)) => (path.to_string(), Some(query.to_string())),
+            None => (target.to_string(), None),
+        };
+        entry.method = method;
+        entry.host = host;
+        entry.path = path;
+        entry.query = query;
+        entry.protocol = protocol;
+    }
+    if let Some(status) = caps.name("status") {
+        entry.status = parse_status(status.as_str())?;
+    }
+    // A "-" size means no body was sent.
+    if let Some(size) = caps.name("size") {
+        entry.size = match size.as_str() {
+            "-" => 0,
+            size => size.parse::<u64>().map_err(|source| ParseError::InvalidSize {
+                value: size.to_string(),
+                source,
+            })?,
+        };
+    }
+    // `%D` logs microseconds and `%T` whole seconds; prefer the finer one if both are present.
+    if let Some(micros) = caps.name("duration_us") {
+        entry.duration = Some(Duration::from_micros(parse_duration_value(micros.as_str())?));
+    } else if let Some(secs) = caps.name("duration_s") {
+        entry.duration = Some(Duration::from_secs(parse_duration_value(secs.as_str())?));
+    }
+    entry.referrer = caps.name("referrer").and_then(|m| optional_field(&unescape_quoted(m.as_str())));
+    entry.user_agent = caps.name("user_agent").and_then(|m| optional_field(&unescape_quoted(m.as_str())));
+
     Ok(entry)
 }
 
+pub(crate) fn parse_duration_value(value: &str) -> Result<u64, ParseError> {
+    value.parse::<u64>().map_err(|source| ParseError::InvalidDuration {
+        value: value.to_string(),
+        source,
+    })
+}
+
+pub(crate) fn parse_status(status: &str) -> Result<u16, ParseError> {
+    match status.parse::<u16>() {
+        Ok(code @ 100..=599) => Ok(code),
+        _ => Err(ParseError::InvalidStatus(status.to_string())),
+    }
+}
+
+// GET /api HTTP/1.1
+// The target sits between the first and last space, so unencoded spaces stay in the path.
+fn parse_request(request: &str) -> Result<(String, String, String), ParseError> {
+    let invalid = || ParseError::InvalidRequest(request.to_string());
+    let (method, rest) = request.split_once(' ').ok_or_else(invalid)?;
+    let (path, protocol) = rest.rsplit_once(' ').ok_or_else(invalid)?;
+
+    let valid_target = path.starts_with('/') || absolute_target_rest(path).is_some();
+    if method.is_empty() || !valid_target || !protocol.starts_with("HTTP/") {
+        return Err(invalid());
+    }
+
+    Ok((method.to_string(), path.to_string(), protocol.to_string()))
+}
+
+// "http://example.com/path" -> "example.com/path"
+fn absolute_target_rest(target: &str) -> Option<&str> {
+    ["http://", "https://"].iter().find_map(|scheme| {
+        let prefix = target.get(..scheme.len())?;
+        prefix.eq_ignore_ascii_case(scheme).then(|| &target[scheme.len()..])
+    })
+}
+
+// Splits the host off an absolute-form target; an empty path becomes `/`.
+pub(crate) fn split_absolute_target(target: &str) -> (Option<String>, Cow<'_, str>) {
+    let Some(rest) = absolute_target_rest(target) else {
+        return (None, Cow::Borrowed(target));
+    };
+    let end = rest.find(['/', '?']).unwrap_or(rest.len());
+    let (host, path) = rest.split_at(end);
+    let path = match path {
+        "" => Cow::Borrowed("/"),
+        path if path.starts_with('?') => Cow::Owned(format!("/{path}")),
+        path => Cow::Borrowed(path),
+    };
+    (Some(host.to_string()), path)
+}
+
+// Apache escapes `"` and `\` inside quoted fields; other escapes are kept verbatim.
+pub(crate) fn unescape_quoted(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next_if(|c| *c == '"' || *c == '\\') {
+                Some(escaped) => unescaped.push(escaped),
+                None => unescaped.push('\\'),
+            },
+            c => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+fn escape_quoted(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// Fields logged as a bare "-" carry no value.
+pub(crate) fn optional_field(value: &str) -> Option<String> {
+    match value {
+        "-" => None,
+        value => Some(value.to_string()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chrono::{TimeZone, Utc};
+    use proptest::prelude::*;
 
     #[test]
     fn test_it_works() {
         let line = "127.0.0.1 - - [01/Jan/2024:12:00:00 +0000] \"GET /api HTTP/1.1\" 200 1234";
 
         let log = parse_common_log(line).unwrap();
-        assert_eq!(log.ip, "127.0.0.1".to_string());
+        assert_eq!(log.ip, IpAddr::V4(Ipv4Addr::LOCALHOST));
         assert_eq!(
             log.timestamp,
-            Utc.with_ymd_and_hms(2024, 01, 01, 12, 0, 0).unwrap()
+            Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap()
         );
         assert_eq!(log.method, "GET".to_string());
         assert_eq!(log.path, "/api".to_string());
+        assert_eq!(log.protocol, "HTTP/1.1".to_string());
         assert_eq!(log.status, 200);
         assert_eq!(log.size, 1234);
     }
@@ -85,6 +923,7 @@ mod tests {
         pub const MALFORMED_LOGS: [&str; 3] = [
             "invalid line with improper formatting",
             "incomplete line [15/Jan/2024:10:24:00 +0000]",
+            // The dashes are valid identity/user placeholders; the missing IP is the error.
             "- - - [15/Jan/2024:10:25:00 +0000] \"GET /no-ip HTTP/1.1\" 200 50"
         ];
         for line in MALFORMED_LOGS.iter() {
@@ -93,6 +932,46 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_field_count_errors() {
+        let common = "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"GET /api HTTP/1.1\" 200 567";
+        let cases = [
+            (
+                "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"GET /api HTTP/1.1\" 200",
+                ParseError::TooFewFields { expected: 7, found: 6 },
+            ),
+            (
+                "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"GET /ap",
+                ParseError::TooFewFields { expected: 7, found: 4 },
+            ),
+            ("10.0.0.5 - - [15/Jan/20", ParseError::TooFewFields { expected: 7, found: 3 }),
+            (
+                "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"GET /api HTTP/1.1\" 200 567 1234",
+                ParseError::UnexpectedTrailing("1234".to_string()),
+            ),
+            (
+                "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"GET /a\\\"b HTTP/1.1\" 200 567 \"-\" \"curl/8.0\"",
+                ParseError::UnexpectedTrailing("\"-\" \"curl/8.0\"".to_string()),
+            ),
+            ("10.0.0.5 - [15/Jan/2024:10:24:12 +0000] \"GET /api HTTP/1.1\" 200 567", ParseError::InvalidFormat),
+            ("10.0.0.5 - - [15/Jan/2024:10:24:12 +0000]\"GET /api HTTP/1.1\" 200 567", ParseError::InvalidFormat),
+        ];
+
+        for (line, expected) in cases {
+            assert_eq!(parse_common_log(line).unwrap_err(), expected, "Wrong result in entry: {line}");
+        }
+
+        let combined = format!("{common} \"-\" \"curl/8.0\" extra");
+        assert_eq!(
+            parse_combined_log(&combined).unwrap_err(),
+            ParseError::UnexpectedTrailing("extra".to_string())
+        );
+        assert_eq!(
+            parse_combined_log(&format!("{common} \"-\"")).unwrap_err(),
+            ParseError::TooFewFields { expected: 9, found: 8 }
+        );
+    }
+
     #[test]
     fn test_invalid_date() {
         const INVALID_DATES: [&str; 3] = [
@@ -103,7 +982,7 @@ mod tests {
 
         for line in INVALID_DATES.iter() {
             let log = parse_common_log(line);
-            assert!(matches!(log, Err(ParseError::InvalidTimestamp)));
+            assert!(matches!(log, Err(ParseError::InvalidTimestamp { .. })));
         }
     }
 
@@ -115,41 +994,833 @@ mod tests {
             "8.8.8.8 - - [15/Jan/2024:10:29:47 +0000] \"DELETE /users HTTP/1.1\" 403 89",
         ];
         let expected = [
-            LogEntry {
-                ip: "10.0.0.5".to_string(),
-                timestamp: Utc.with_ymd_and_hms(2024, 01, 15, 10, 24, 12).unwrap(),
-                method: "POST".to_string(),
-                path: "/api/login".to_string(),
-                status: 201,
-                size: 567
-            },
-            LogEntry {
-                ip: "203.0.113.42".to_string(),
-                timestamp: Utc.with_ymd_and_hms(2024, 01, 15, 10, 27, 15).unwrap(),
-                method: "PUT".to_string(),
-                path: "/api/products".to_string(),
-                status: 500,
-                size: 2048
-            },
-            LogEntry {
-                ip: "8.8.8.8".to_string(),
-                timestamp: Utc.with_ymd_and_hms(2024, 01, 15, 10, 29, 47).unwrap(),
-                method: "DELETE".to_string(),
-                path: "/users".to_string(),
-                status: 403,
-                size: 89
-            }
+            LogEntry::builder()
+                .ip(Ipv4Addr::new(10, 0, 0, 5))
+                .timestamp(Utc.with_ymd_and_hms(2024, 1, 15, 10, 24, 12).unwrap())
+                .method("POST")
+                .path("/api/login")
+                .protocol("HTTP/1.1")
+                .status(201)
+                .size(567)
+                .build(),
+            LogEntry::builder()
+                .ip(Ipv4Addr::new(203, 0, 113, 42))
+                .timestamp(Utc.with_ymd_and_hms(2024, 1, 15, 10, 27, 15).unwrap())
+                .method("PUT")
+                .path("/api/products")
+                .protocol("HTTP/1.1")
+                .status(500)
+                .size(2048)
+                .build(),
+            LogEntry::builder()
+                .ip(Ipv4Addr::new(8, 8, 8, 8))
+                .timestamp(Utc.with_ymd_and_hms(2024, 1, 15, 10, 29, 47).unwrap())
+                .method("DELETE")
+                .path("/users")
+                .protocol("HTTP/1.1")
+                .status(403)
+                .size(89)
+                .build(),
         ];
 
         for (line, expected) in valid_logs.iter().zip(expected.iter()) {
-            let log = parse_common_log(line).unwrap();
+            assert_eq!(parse_common_log(line).unwrap(), *expected);
+        }
+    }
+
+    #[test]
+    fn test_combined_log() {
+        let line = "127.0.0.1 - - [01/Jan/2024:12:00:00 +0000] \"GET /api HTTP/1.1\" 200 1234 \"http://example.com/start\" \"Mozilla/5.0 (X11; Linux x86_64)\"";
+
+        let log = parse_combined_log(line).unwrap();
+        assert_eq!(log.ip, IpAddr::V4(Ipv4Addr::LOCALHOST));
+        assert_eq!(log.path, "/api".to_string());
+        assert_eq!(log.size, 1234);
+        assert_eq!(log.referrer, Some("http://example.com/start".to_string()));
+        assert_eq!(log.user_agent, Some("Mozilla/5.0 (X11; Linux x86_64)".to_string()));
+    }
 
-            assert_eq!(log.ip, expected.ip);
-            assert_eq!(log.timestamp, expected.timestamp);
-            assert_eq!(log.method, expected.method);
-            assert_eq!(log.path, expected.path);
-            assert_eq!(log.status, expected.status);
-            assert_eq!(log.size, expected.size);
+    #[test]
+    fn test_combined_log_placeholders() {
+        let line = "127.0.0.1 - - [01/Jan/2024:12:00:00 +0000] \"GET /api HTTP/1.1\" 200 1234 \"-\" \"-\"";
+
+        let log = parse_combined_log(line).unwrap();
+        assert_eq!(log.referrer, None);
+        assert_eq!(log.user_agent, None);
+
+        let common = "127.0.0.1 - - [01/Jan/2024:12:00:00 +0000] \"GET /api HTTP/1.1\" 200 1234";
+        let log = parse_common_log(common).unwrap();
+        assert_eq!(log.referrer, None);
+        assert_eq!(log.user_agent, None);
+        assert_eq!(
+            parse_combined_log(common).unwrap_err(),
+            ParseError::TooFewFields { expected: 9, found: 7 }
+        );
+    }
+
+    #[test]
+    fn test_vhost_combined_log() {
+        let combined = "127.0.0.1 - - [01/Jan/2024:12:00:00 +0000] \"GET /api HTTP/1.1\" 200 1234 \"-\" \"curl/8.0\"";
+        let line = format!("shop.example.com {combined}");
+
+        let log = parse_vhost_combined_log(&line).unwrap();
+        assert_eq!(log.vhost, Some("shop.example.com".to_string()));
+        assert_eq!(log.ip, IpAddr::V4(Ipv4Addr::LOCALHOST));
+        assert_eq!(log.path, "/api");
+        assert_eq!(log.user_agent, Some("curl/8.0".to_string()));
+        assert_eq!(parse_combined_log(combined).unwrap().vhost, None);
+        assert_eq!(parse_combined_log(&line).unwrap_err(), ParseError::InvalidFormat);
+
+        assert_eq!(parse_vhost_combined_log(&format!("- {combined}")).unwrap().vhost, None);
+        assert_eq!(parse_vhost_combined_log(combined).unwrap_err(), ParseError::InvalidFormat);
+        let truncated = "shop.example.com 127.0.0.1 - - [01/Jan/2024:12:00:00 +0000] \"GET /api HTTP/1.1\" 200";
+        assert_eq!(
+            parse_vhost_combined_log(truncated).unwrap_err(),
+            ParseError::TooFewFields { expected: 10, found: 7 }
+        );
+    }
+
+    #[test]
+    fn test_identity_and_user() {
+        let line = "127.0.0.1 user-identifier frank [10/Oct/2000:13:55:36 -0700] \"GET /apache_pb.gif HTTP/1.0\" 200 2326";
+
+        let log = parse_common_log(line).unwrap();
+        assert_eq!(log.identity, Some("user-identifier".to_string()));
+        assert_eq!(log.user, Some("frank".to_string()));
+
+        let line = "127.0.0.1 - frank [10/Oct/2000:13:55:36 -0700] \"GET /apache_pb.gif HTTP/1.0\" 200 2326";
+        let log = parse_common_log(line).unwrap();
+        assert_eq!(log.identity, None);
+        assert_eq!(log.user, Some("frank".to_string()));
+    }
+
+    #[test]
+    fn test_ipv6_addresses() {
+        let cases = [
+            ("2001:0db8:85a3:0000:0000:8a2e:0370:7334", "2001:db8:85a3::8a2e:370:7334"),
+            ("2001:db8::1", "2001:db8::1"),
+            ("::1", "::1"),
+            ("::ffff:192.0.2.128", "::ffff:192.0.2.128"),
+        ];
+
+        for (ip, expected) in cases.iter() {
+            let line = format!("{ip} - - [15/Jan/2024:10:24:12 +0000] \"GET /api HTTP/1.1\" 200 567");
+            let log = parse_common_log(&line).unwrap();
+            assert_eq!(log.ip, expected.parse::<IpAddr>().unwrap(), "Wrong ip in entry: {line}");
+            assert!(log.ip.is_ipv6());
+        }
+    }
+
+    #[test]
+    fn test_invalid_ip() {
+        const INVALID_IPS: [&str; 3] = [
+            "999.999.999.999 - - [15/Jan/2024:10:24:12 +0000] \"GET /api HTTP/1.1\" 200 567",
+            "10.0.0 - - [15/Jan/2024:10:24:12 +0000] \"GET /api HTTP/1.1\" 200 567",
+            "2001:db8:::1 - - [15/Jan/2024:10:24:12 +0000] \"GET /api HTTP/1.1\" 200 567",
+        ];
+
+        for line in INVALID_IPS.iter() {
+            let log = parse_common_log(line);
+            assert!(matches!(log, Err(ParseError::InvalidIp { .. })), "Wrong result in entry: {line}");
+        }
+    }
+
+    #[test]
+    fn test_ipv4_octet_range() {
+        let cases = [
+            ("255.255.255.255", true),
+            ("0.0.0.0", true),
+            ("256.1.1.1", false),
+            ("1.1.1.256", false),
+            ("300.400.500.600", false),
+            ("010.0.0.1", false),
+        ];
+
+        for (ip, valid) in cases.iter() {
+            let line = format!("{ip} - - [15/Jan/2024:10:24:12 +0000] \"GET /api HTTP/1.1\" 200 567");
+            let log = parse_common_log(&line);
+            if *valid {
+                assert_eq!(log.unwrap().ip.to_string(), *ip);
+            } else {
+                let err = log.unwrap_err();
+                assert!(matches!(err, ParseError::InvalidIp { value, .. } if value == *ip), "Wrong result for ip: {ip}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_dash_size() {
+        let line = "172.16.0.10 - - [15/Jan/2024:10:25:33 +0000] \"GET /static/image.png HTTP/1.1\" 304 -";
+
+        let log = parse_common_log(line).unwrap();
+        assert_eq!(log.status, 304);
+        assert_eq!(log.size, 0);
+
+        let line = "172.16.0.10 - - [15/Jan/2024:10:25:33 +0000] \"GET /static/image.png HTTP/1.1\" 304 - \"-\" \"curl/8.0\"";
+        let log = parse_combined_log(line).unwrap();
+        assert_eq!(log.size, 0);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let line = "127.0.0.1 - frank [01/Jan/2024:12:00:00 +0000] \"GET /api HTTP/1.1\" 200 1234 \"-\" \"curl/8.0\"";
+        let log = parse_combined_log(line).unwrap();
+
+        let json = serde_json::to_string(&log).unwrap();
+        assert!(json.contains("\"timestamp\":\"2024-01-01T12:00:00Z\""), "Wrong json: {json}");
+
+        let decoded: LogEntry = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.ip, log.ip);
+        assert_eq!(decoded.identity, log.identity);
+        assert_eq!(decoded.user, log.user);
+        assert_eq!(decoded.timestamp, log.timestamp);
+        assert_eq!(decoded.method, log.method);
+        assert_eq!(decoded.path, log.path);
+        assert_eq!(decoded.status, log.status);
+        assert_eq!(decoded.size, log.size);
+        assert_eq!(decoded.referrer, log.referrer);
+        assert_eq!(decoded.user_agent, log.user_agent);
+    }
+
+    #[test]
+    fn test_timestamp_keeps_offset() {
+        let line = "10.0.0.5 - - [15/Jan/2024:15:54:12 +0530] \"POST /api/login HTTP/1.1\" 201 567";
+
+        let log = parse_common_log(line).unwrap();
+        assert_eq!(log.timestamp.offset(), &FixedOffset::east_opt(5 * 3600 + 30 * 60).unwrap());
+        assert_eq!(log.timestamp.to_string(), "2024-01-15 15:54:12 +05:30");
+        assert_eq!(
+            log.timestamp.to_utc(),
+            Utc.with_ymd_and_hms(2024, 1, 15, 10, 24, 12).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_protocol_versions() {
+        for protocol in ["HTTP/1.0", "HTTP/1.1", "HTTP/2.0"] {
+            let line = format!("10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"GET /api {protocol}\" 200 567");
+            let log = parse_common_log(&line).unwrap();
+            assert_eq!(log.protocol, protocol);
+        }
+    }
+
+    #[test]
+    fn test_invalid_request() {
+        const INVALID_REQUESTS: [&str; 5] = [
+            "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"GET\" 200 567",
+            "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"GET /api\" 200 567",
+            "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"GET api HTTP/1.1\" 200 567",
+            "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"GET /api HTTP/1.1 extra\" 200 567",
+            "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"GET  HTTP/1.1\" 200 567",
+        ];
+
+        for line in INVALID_REQUESTS.iter() {
+            let log = parse_common_log(line);
+            assert!(matches!(log, Err(ParseError::InvalidRequest(_))), "Wrong result in entry: {line}");
+        }
+    }
+
+    #[test]
+    fn test_missing_request_line() {
+        let line = "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"-\" 408 -";
+        let log = parse_common_log(line).unwrap();
+        assert_eq!((log.method.as_str(), log.path.as_str(), log.protocol.as_str()), ("", "", ""));
+        assert_eq!(log.query, None);
+        assert_eq!(log.status, 408);
+        assert_eq!(log.to_string(), "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"-\" 408 0");
+
+        let options = ParseOptions {
+            strict_methods: true,
+            ..Default::default()
+        };
+        assert!(parse_common_log_with(line, &options).is_ok());
+
+        let combined = "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"-\" 400 0 \"-\" \"-\"";
+        assert_eq!(parse_combined_log(combined).unwrap().status, 400);
+    }
+
+    #[test]
+    fn test_empty_request_line() {
+        let line = "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"\" 400 0 \"-\" \"-\"";
+        let log = parse_combined_log(line).unwrap();
+        assert_eq!((log.method.as_str(), log.path.as_str(), log.protocol.as_str()), ("", "", ""));
+        assert_eq!(log.status, 400);
+        assert_eq!(log.user_agent, None);
+
+        let common = "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"\" 499 -";
+        assert_eq!(parse_common_log(common).unwrap().status, 499);
+
+        let partial = "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"GET\" 400 0";
+        assert!(matches!(parse_common_log(partial), Err(ParseError::InvalidRequest(_))));
+    }
+
+    #[test]
+    fn test_request_target_forms() {
+        let cases = [
+            ("/path/to?x=1", None, "/path/to", Some("x=1")),
+            ("http://example.com/path/to?x=1", Some("example.com"), "/path/to", Some("x=1")),
+            ("HTTPS://example.com:8443/api", Some("example.com:8443"), "/api", None),
+            ("http://example.com", Some("example.com"), "/", None),
+            ("http://example.com?x=1", Some("example.com"), "/", Some("x=1")),
+        ];
+
+        for (target, host, path, query) in cases {
+            let line = format!("10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"GET {target} HTTP/1.1\" 200 567");
+            let log = parse_common_log(&line).unwrap();
+            assert_eq!(log.host.as_deref(), host, "Wrong host for {target}");
+            assert_eq!(log.path, path, "Wrong path for {target}");
+            assert_eq!(log.query.as_deref(), query, "Wrong query for {target}");
+        }
+
+        let line = "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"GET http://example.com/a HTTP/1.1\" 200 567";
+        assert_eq!(parse_common_log(line).unwrap().to_string(), line);
+
+        let line = "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"GET ftp://example.com/a HTTP/1.1\" 200 567";
+        assert!(matches!(parse_common_log(line), Err(ParseError::InvalidRequest(_))));
+    }
+
+    #[test]
+    fn test_query_string() {
+        let cases = [
+            ("/api/users?id=5", "/api/users", Some("id=5")),
+            ("/search?q=a?b&page=2", "/search", Some("q=a?b&page=2")),
+            ("/api/users?", "/api/users", Some("")),
+            ("/api/users", "/api/users", None),
+        ];
+
+        for (target, path, query) in cases.iter() {
+            let line = format!("10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"GET {target} HTTP/1.1\" 200 567");
+            let log = parse_common_log(&line).unwrap();
+            assert_eq!(log.path, *path);
+            assert_eq!(log.query.as_deref(), *query);
+        }
+    }
+
+    #[test]
+    fn test_error_values() {
+        let cases = [
+            (
+                "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"GET /api HTTP/1.1\" 20x 567",
+                ParseError::InvalidStatus("20x".to_string()),
+            ),
+            (
+                "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"GET /api HTTP/1.1\" 200 12k",
+                ParseError::InvalidSize {
+                    value: "12k".to_string(),
+                    source: "12k".parse::<u64>().unwrap_err(),
+                },
+            ),
+            (
+                "10.0.0.5 - - [15-Jan-2024 10:24:12] \"GET /api HTTP/1.1\" 200 567",
+                ParseError::InvalidTimestamp {
+                    value: "15-Jan-2024 10:24:12".to_string(),
+                    source: DateTime::parse_from_str("15-Jan-2024 10:24:12", CLF_TIMESTAMP_FORMAT).err(),
+                },
+            ),
+            (
+                "10.0.0.500 - - [15/Jan/2024:10:24:12 +0000] \"GET /api HTTP/1.1\" 200 567",
+                ParseError::InvalidIp {
+                    value: "10.0.0.500".to_string(),
+                    source: "10.0.0.500".parse::<IpAddr>().unwrap_err(),
+                },
+            ),
+            (
+                "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"GET\" 200 567",
+                ParseError::InvalidRequest("GET".to_string()),
+            ),
+        ];
+
+        for (line, expected) in cases.iter() {
+            assert_eq!(parse_common_log(line).unwrap_err(), *expected, "Wrong result in entry: {line}");
+        }
+        assert_eq!(
+            ParseError::InvalidStatus("20x".to_string()).to_string(),
+            "invalid status code: \"20x\""
+        );
+    }
+
+    #[test]
+    fn test_error_sources() {
+        use std::error::Error as _;
+
+        let line = "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"GET /api HTTP/1.1\" 200 12k";
+        let err = parse_common_log(line).unwrap_err();
+        assert_eq!(err.to_string(), "invalid size: \"12k\"");
+        assert_eq!(err.source().unwrap().to_string(), "invalid digit found in string");
+
+        let line = "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000 \"GET /api HTTP/1.1\" 200 567";
+        let err = parse_common_log(line).unwrap_err();
+        assert!(err.source().is_none(), "{err:?}");
+
+        let line = "10.0.0.5 - - [15/Jan/2024:10:24:12] \"GET /api HTTP/1.1\" 200 567";
+        let err = parse_common_log(line).unwrap_err();
+        assert!(err.source().unwrap().is::<chrono::ParseError>(), "{err:?}");
+    }
+
+    #[test]
+    fn test_status_range() {
+        for status in ["100", "599", "700", "99", "1000"] {
+            let line = format!("10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"GET /api HTTP/1.1\" {status} 567");
+            let log = parse_common_log(&line);
+            match status {
+                "100" | "599" => assert_eq!(log.unwrap().status, status.parse::<u16>().unwrap()),
+                _ => assert_eq!(log.unwrap_err(), ParseError::InvalidStatus(status.to_string())),
+            }
+        }
+    }
+
+    #[test]
+    fn test_status_class() {
+        let cases = [
+            (0, StatusClass::Unknown),
+            (100, StatusClass::Informational),
+            (199, StatusClass::Informational),
+            (200, StatusClass::Success),
+            (304, StatusClass::Redirection),
+            (404, StatusClass::ClientError),
+            (503, StatusClass::ServerError),
+            (600, StatusClass::Unknown),
+        ];
+
+        for (status, expected) in cases.iter() {
+            let entry = LogEntry {
+                status: *status,
+                ..Default::default()
+            };
+            assert_eq!(entry.status_class(), *expected, "Wrong class for status {status}");
+        }
+    }
+
+    #[test]
+    fn test_path_segments() {
+        let cases: [(&str, &[&str]); 5] = [
+            ("/api/users/42", &["api", "users", "42"]),
+            ("/api//users/", &["api", "users"]),
+            ("/", &[]),
+            ("", &[]),
+            ("relative/path", &["relative", "path"]),
+        ];
+
+        for (path, expected) in cases {
+            let entry = LogEntry::builder().path(path).build();
+            assert_eq!(entry.path_segments(), expected, "Wrong segments for {path:?}");
+        }
+
+        let line = "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"GET /static/app.js?v=2 HTTP/1.1\" 200 0";
+        assert_eq!(parse_common_log(line).unwrap().path_segments(), ["static", "app.js"]);
+    }
+
+    #[test]
+    fn test_format_size() {
+        let cases = [
+            (0, "0 B", "0 B"),
+            (999, "999 B", "999 B"),
+            (1000, "1000 B", "1.0 kB"),
+            (1023, "1023 B", "1.0 kB"),
+            (1024, "1.0 KiB", "1.0 kB"),
+            (1229, "1.2 KiB", "1.2 kB"),
+            (1_048_535, "1.0 MiB", "1.0 MB"),
+            (3_565_158, "3.4 MiB", "3.6 MB"),
+            (u64::MAX, "16.0 EiB", "18.4 EB"),
+        ];
+
+        for (bytes, binary, decimal) in cases {
+            assert_eq!(format_size(bytes, SizeUnits::Binary), binary, "Wrong binary size for {bytes}");
+            assert_eq!(format_size(bytes, SizeUnits::Decimal), decimal, "Wrong decimal size for {bytes}");
+        }
+        assert_eq!(LogEntry::builder().size(1229).build().content_length_human(), "1.2 KiB");
+    }
+
+    #[test]
+    fn test_anonymized_ip() {
+        let cases = [
+            ("203.0.113.42", "203.0.113.0"),
+            ("10.0.0.0", "10.0.0.0"),
+            ("255.255.255.255", "255.255.255.0"),
+            ("2001:db8:85a3:8d3:1319:8a2e:370:7348", "2001:db8:85a3::"),
+            ("2001:db8:85a3:ffff::1", "2001:db8:85a3::"),
+            ("::1", "::"),
+        ];
+
+        for (ip, expected) in cases {
+            let entry = LogEntry::builder().ip(ip.parse::<IpAddr>().unwrap()).build();
+            assert_eq!(entry.anonymized_ip().to_string(), expected, "Wrong mask for {ip}");
+            assert_eq!(entry.ip.to_string(), ip);
+        }
+    }
+
+    #[test]
+    fn test_status_predicates() {
+        let cases = [
+            (199, [false, false, false, false]),
+            (200, [true, false, false, false]),
+            (299, [true, false, false, false]),
+            (300, [false, true, false, false]),
+            (399, [false, true, false, false]),
+            (400, [false, false, true, false]),
+            (499, [false, false, true, false]),
+            (500, [false, false, false, true]),
+            (599, [false, false, false, true]),
+            (600, [false, false, false, false]),
+        ];
+
+        for (status, expected) in cases {
+            let entry = LogEntry::builder().status(status).build();
+            let actual = [entry.is_success(), entry.is_redirect(), entry.is_client_error(), entry.is_server_error()];
+            assert_eq!(actual, expected, "Wrong predicates for status {status}");
+            assert_eq!(entry.is_error(), expected[2] || expected[3]);
+        }
+    }
+
+    #[test]
+    fn test_builder_defaults() {
+        let entry = LogEntry::builder().path("/health").status(204).build();
+
+        assert_eq!(entry.path, "/health");
+        assert_eq!(entry.status, 204);
+        assert_eq!(entry.ip, IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+        assert_eq!(entry.method, "");
+        assert_eq!(entry.referrer, None);
+        assert_eq!(entry.timestamp, DateTime::<FixedOffset>::default());
+
+        let entry = LogEntry::builder().user_agent("curl/8.0").query("").build();
+        assert_eq!(entry.user_agent, Some("curl/8.0".to_string()));
+        assert_eq!(entry.query, Some(String::new()));
+    }
+
+    #[test]
+    fn test_strict_methods() {
+        let line = "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"PROPFIND /dav HTTP/1.1\" 207 512";
+        assert_eq!(parse_common_log(line).unwrap().method, "PROPFIND");
+
+        let strict = ParseOptions {
+            strict_methods: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            parse_common_log_with(line, &strict).unwrap_err(),
+            ParseError::InvalidMethod("PROPFIND".to_string())
+        );
+
+        for method in HTTP_METHODS {
+            let line = format!("10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"{method} /api HTTP/1.1\" 200 0");
+            assert_eq!(parse_common_log_with(&line, &strict).unwrap().method, method);
+        }
+
+        let lowercase = "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"get /api HTTP/1.1\" 200 0";
+        assert!(matches!(parse_common_log_with(lowercase, &strict), Err(ParseError::InvalidMethod(_))));
+    }
+
+    #[test]
+    fn test_uppercase_methods() {
+        let options = ParseOptions {
+            uppercase_methods: true,
+            ..Default::default()
+        };
+        for method in ["get", "Get", "gEt", "GET"] {
+            let line = format!("10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"{method} /api HTTP/1.1\" 200 0");
+            assert_eq!(parse_common_log(&line).unwrap().method, method);
+            assert_eq!(parse_common_log_with(&line, &options).unwrap().method, "GET");
+        }
+
+        let strict = ParseOptions {
+            strict_methods: true,
+            ..options
+        };
+        let line = "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"Delete /api HTTP/1.1\" 200 0";
+        assert_eq!(parse_common_log_with(line, &strict).unwrap().method, "DELETE");
+        let line = "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"propfind /dav HTTP/1.1\" 207 0";
+        assert_eq!(
+            parse_common_log_with(line, &strict).unwrap_err(),
+            ParseError::InvalidMethod("PROPFIND".to_string())
+        );
+    }
+
+    #[test]
+    fn test_escaped_quotes_in_request() {
+        let cases = [
+            (r#"GET /search?q=\"rust\" HTTP/1.1"#, "/search", Some(r#"q="rust""#)),
+            (r#"GET /a%22b HTTP/1.1"#, "/a%22b", None),
+            (r#"GET /say \"hi\" there HTTP/1.1"#, r#"/say "hi" there"#, None),
+            (r#"GET /my%20file.txt HTTP/1.1"#, "/my%20file.txt", None),
+            (r#"GET /back\\slash HTTP/1.1"#, r"/back\slash", None),
+        ];
+
+        for (request, path, query) in cases.iter() {
+            let line = format!(r#"10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] "{request}" 200 567"#);
+            let log = parse_common_log(&line).unwrap();
+            assert_eq!(log.path, *path, "Wrong result in entry: {line}");
+            assert_eq!(log.query.as_deref(), *query);
+            assert_eq!(log.status, 200);
+            assert_eq!(log.size, 567);
+        }
+
+        let line = r#"10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] "GET /a\" 404 0 HTTP/1.1" 200 567 "-" "Bot \"quoted\"/1.0""#;
+        let log = parse_combined_log(line).unwrap();
+        assert_eq!(log.path, r#"/a" 404 0"#);
+        assert_eq!(log.status, 200);
+        assert_eq!(log.user_agent, Some(r#"Bot "quoted"/1.0"#.to_string()));
+    }
+
+    #[test]
+    fn test_display_as_common_log() {
+        let line = r#"10.0.0.5 - frank [15/Jan/2024:10:24:12 -0700] "GET /search?q=\"rust\" HTTP/1.1" 200 567"#;
+        let log = parse_common_log(line).unwrap();
+        assert_eq!(log.to_string(), line);
+
+        let log = LogEntry::builder()
+            .ip(Ipv4Addr::new(8, 8, 8, 8))
+            .method("POST")
+            .path("/api")
+            .protocol("HTTP/1.0")
+            .status(201)
+            .build();
+        assert_eq!(
+            log.to_string(),
+            "8.8.8.8 - - [01/Jan/1970:00:00:00 +0000] \"POST /api HTTP/1.0\" 201 0"
+        );
+    }
+
+    fn log_entries() -> impl Strategy<Value = LogEntry> {
+        (
+            any::<IpAddr>(),
+            proptest::option::of("[a-z0-9_]{1,8}"),
+            proptest::option::of("[a-z0-9_]{1,8}"),
+            (0i64..4_102_444_800, -14 * 60..=14 * 60i32),
+            prop::sample::select(&HTTP_METHODS[..]),
+            r#"/[a-zA-Z0-9/._%" \\-]{0,24}"#,
+            proptest::option::of(r#"[a-z0-9=&?"\\]{0,12}"#),
+            (100u16..=599, any::<u64>()),
+        )
+            .prop_map(|(ip, identity, user, (secs, offset), method, path, query, (status, size))| {
+                let offset = FixedOffset::east_opt(offset * 60).unwrap();
+                LogEntry {
+                    ip,
+                    identity,
+                    user,
+                    timestamp: DateTime::from_timestamp(secs, 0).unwrap().with_timezone(&offset),
+                    method: method.to_string(),
+                    path,
+                    query,
+                    protocol: "HTTP/1.1".to_string(),
+                    status,
+                    size,
+                    ..Default::default()
+                }
+            })
+    }
+
+    proptest! {
+        #[test]
+        fn test_display_round_trip(entry in log_entries()) {
+            let parsed = parse_common_log(&entry.to_string()).unwrap();
+            prop_assert_eq!(parsed.timestamp.offset(), entry.timestamp.offset());
+            prop_assert_eq!(parsed, entry);
         }
     }
+
+    #[test]
+    fn test_decoded_path() {
+        let cases = [
+            ("/search/%20hello", "/search/ hello"),
+            ("/caf%C3%A9", "/café"),
+            ("/a%2Fb", "/a/b"),
+            ("/plain", "/plain"),
+            ("/100%", "/100%"),
+            ("/bad%zzescape%2", "/bad%zzescape%2"),
+            ("/half%C3", "/half\u{FFFD}"),
+        ];
+
+        for (path, decoded) in cases.iter() {
+            let entry = LogEntry::builder().path(*path).build();
+            assert_eq!(entry.decoded_path(), *decoded, "Wrong result for path: {path}");
+            assert_eq!(entry.path, *path);
+        }
+    }
+
+    #[test]
+    fn test_referrer_host() {
+        let cases = [
+            ("https://www.example.com/page?q=1", Some("www.example.com")),
+            ("http://EXAMPLE.com:8080/", Some("example.com")),
+            ("https://[2001:db8::1]/", Some("[2001:db8::1]")),
+            ("android-app://com.google.android.gm/", Some("com.google.android.gm")),
+            ("about:blank", None),
+            ("/relative/path", None),
+            ("garbage", None),
+        ];
+
+        for (referrer, host) in cases.iter() {
+            let entry = LogEntry::builder().referrer(*referrer).build();
+            assert_eq!(entry.referrer_host().as_deref(), *host, "Wrong result for referrer: {referrer}");
+        }
+        assert_eq!(LogEntry::default().referrer_host(), None);
+    }
+
+    #[test]
+    fn test_timestamp_formats() {
+        let with_format = |timestamp: &str, format: &str| {
+            let line = format!("10.0.0.5 - - [{timestamp}] \"GET /api HTTP/1.1\" 200 567");
+            let options = ParseOptions {
+                timestamp_format: format.parse().unwrap(),
+                ..Default::default()
+            };
+            parse_common_log_with(&line, &options).map(|log| log.timestamp)
+        };
+        let expected = Utc.with_ymd_and_hms(2024, 1, 15, 10, 24, 12).unwrap();
+
+        assert_eq!(with_format("2024-01-15T10:24:12Z", "%+").unwrap(), expected);
+        let offset = with_format("2024-01-15T12:24:12+02:00", "%+").unwrap();
+        assert_eq!(offset, expected);
+        assert_eq!(offset.offset().local_minus_utc(), 2 * 3600);
+        assert_eq!(with_format("2024-01-15 10:24:12", "%Y-%m-%d %H:%M:%S").unwrap(), expected);
+        assert_eq!(with_format("1705314252", "epoch").unwrap(), expected);
+        assert_eq!(with_format("1705314252000", "epoch_ms").unwrap(), expected);
+        assert_eq!(with_format("15/Jan/2024:10:24:12 +0000", "%d/%b/%Y:%H:%M:%S %z").unwrap(), expected);
+
+        assert_eq!(
+            with_format("1705314252.5", "epoch").unwrap_err(),
+            ParseError::InvalidTimestamp {
+                value: "1705314252.5".to_string(),
+                source: None
+            }
+        );
+        assert!(matches!(
+            with_format("15/Jan/2024:10:24:12 +0000", "epoch"),
+            Err(ParseError::InvalidTimestamp { source: None, .. })
+        ));
+        assert!(matches!(
+            with_format("15/Jan/2024:10:24:12 +0000", "%+"),
+            Err(ParseError::InvalidTimestamp { source: Some(_), .. })
+        ));
+    }
+
+    #[test]
+    fn test_keep_raw_timestamp() {
+        let line = "10.0.0.5 - - [15/Jan/2024:16:24:12 +0600] \"GET /api HTTP/1.1\" 200 567";
+        assert_eq!(parse_common_log(line).unwrap().raw_timestamp, None);
+
+        let options = ParseOptions {
+            keep_raw_timestamp: true,
+            ..Default::default()
+        };
+        let entry = parse_common_log_with(line, &options).unwrap();
+        assert_eq!(entry.raw_timestamp.as_deref(), Some("15/Jan/2024:16:24:12 +0600"));
+        assert_eq!(entry.timestamp, Utc.with_ymd_and_hms(2024, 1, 15, 10, 24, 12).unwrap());
+    }
+
+    #[test]
+    fn test_parse_many() {
+        let lines = [
+            "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"GET /a HTTP/1.1\" 200 567",
+            "garbage",
+            "10.0.0.5 - - [15/Jan/2024:10:24:13 +0000] \"GET /b HTTP/1.1\" 200 567",
+            "10.0.0.5 - - [15/Jan/2024:10:24:14 +0000] \"GET /c HTTP/1.1\" abc 567",
+        ];
+        let (entries, errors) = parse_many(lines);
+        assert_eq!(entries.iter().map(|e| e.path.as_str()).collect::<Vec<_>>(), ["/a", "/b"]);
+        assert_eq!(
+            errors,
+            [
+                (1, ParseError::InvalidFormat),
+                (3, ParseError::InvalidStatus("abc".to_string())),
+            ]
+        );
+
+        let (entries, errors) = parse_many([]);
+        assert!(entries.is_empty() && errors.is_empty());
+    }
+
+    #[test]
+    fn test_timestamp_padding_and_month_names() {
+        let expected = Utc.with_ymd_and_hms(2024, 3, 15, 10, 24, 12).unwrap();
+        let padded = [" 15/Mar/2024:10:24:12 +0000 ", "15/Mar/2024:10:24:12 +0000  ", "\t15/Mar/2024:10:24:12 +0000"];
+        for timestamp in padded {
+            let line = format!("10.0.0.5 - - [{timestamp}] \"GET /api HTTP/1.1\" 200 567");
+            assert_eq!(parse_common_log(&line).unwrap().timestamp, expected, "Wrong result for {timestamp:?}");
+        }
+
+        let german = "10.0.0.5 - - [15/Mär/2024:10:24:12 +0000] \"GET /api HTTP/1.1\" 200 567";
+        assert!(matches!(
+            parse_common_log(german),
+            Err(ParseError::InvalidTimestamp { value, .. }) if value == "15/Mär/2024:10:24:12 +0000"
+        ));
+        let names = ["Jan", "Feb", "Mär", "Apr", "Mai", "Jun", "Jul", "Aug", "Sep", "Okt", "Nov", "Dez"];
+        let options = ParseOptions {
+            month_names: Some(names.map(str::to_string)),
+            ..Default::default()
+        };
+        assert_eq!(parse_common_log_with(german, &options).unwrap().timestamp, expected);
+        let english = "10.0.0.5 - - [15/Mar/2024:10:24:12 +0000] \"GET /api HTTP/1.1\" 200 567";
+        assert_eq!(parse_common_log_with(english, &options).unwrap().timestamp, expected);
+
+        let french = [
+            "janv.", "févr.", "mars", "avr.", "mai", "juin", "juil.", "août", "sept.", "oct.", "nov.", "déc.",
+        ];
+        assert_eq!(
+            with_english_month("15/juil./2024:10:24:12 +0000", &french.map(str::to_string)).as_deref(),
+            Some("15/Jul/2024:10:24:12 +0000")
+        );
+    }
+
+    #[test]
+    fn test_from_str() {
+        let line = "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"POST /api/login HTTP/1.1\" 201 567";
+        let entry: LogEntry = line.parse().unwrap();
+        assert_eq!(entry.path, "/api/login");
+        assert_eq!(entry.status, 201);
+
+        assert_eq!("not a log line".parse::<LogEntry>().unwrap_err(), ParseError::InvalidFormat);
+    }
+
+    #[test]
+    fn test_trailing_whitespace_and_crlf() {
+        let common = "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"GET /api HTTP/1.1\" 200 1234";
+        let combined = format!("{common} \"-\" \"curl/8.0\"");
+
+        for suffix in ["\r", "\r\n", "  ", "\t\r\n"] {
+            let log = parse_common_log(&format!("{common}{suffix}")).unwrap();
+            assert_eq!(log.size, 1234, "Wrong result for suffix: {suffix:?}");
+
+            let log = parse_combined_log(&format!("{combined}{suffix}")).unwrap();
+            assert_eq!(log.user_agent, Some("curl/8.0".to_string()));
+        }
+
+        let input = format!("{common}\r\n{common}\r\n");
+        for line in std::io::BufRead::lines(input.as_bytes()) {
+            assert_eq!(parse_common_log(&line.unwrap()).unwrap().size, 1234);
+        }
+    }
+
+    #[test]
+    fn test_entries_in_hash_set() {
+        let line = "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"GET /api HTTP/1.1\" 200 567";
+        let shifted = "10.0.0.5 - - [15/Jan/2024:12:24:12 +0200] \"GET /api HTTP/1.1\" 200 567";
+        let other = "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"GET /api HTTP/1.1\" 404 567";
+
+        let entries = [line, line, shifted, other]
+            .iter()
+            .map(|line| parse_common_log(line).unwrap())
+            .collect::<std::collections::HashSet<_>>();
+        assert_eq!(entries.len(), 2);
+        assert!(entries.contains(&parse_common_log(other).unwrap()));
+    }
+
+    #[test]
+    fn test_entries_order_by_time() {
+        let late = "10.0.0.1 - - [15/Jan/2024:10:24:13 +0000] \"GET /a HTTP/1.1\" 200 567";
+        let early = "10.0.0.9 - - [15/Jan/2024:12:24:12 +0200] \"GET /z HTTP/1.1\" 500 567";
+        let tie = "10.0.0.9 - - [15/Jan/2024:10:24:12 +0000] \"GET /z HTTP/1.1\" 404 567";
+        let same = "10.0.0.9 - - [15/Jan/2024:10:24:12 +0000] \"GET /z HTTP/1.1\" 500 567";
+
+        let mut entries = [late, early, tie].map(|line| parse_common_log(line).unwrap());
+        entries.sort();
+        let statuses = entries.iter().map(|entry| entry.status).collect::<Vec<_>>();
+        assert_eq!(statuses, [404, 500, 200]);
+
+        // Consistent with `Eq`: the same instant in another offset compares equal.
+        let early = parse_common_log(early).unwrap();
+        assert_eq!(early.cmp(&parse_common_log(same).unwrap()), Ordering::Equal);
+    }
 }
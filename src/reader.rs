@@ -0,0 +1,707 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use flate2::read::MultiGzDecoder;
+use thiserror::Error;
+
+use crate::filter::TimeRange;
+use crate::format::{FormatError, FormatSpec, LogFormat};
+use crate::parser::{
+    LogEntry, ParseError, ParseOptions, parse_combined_log_with, parse_common_log_with, parse_vhost_combined_log_with,
+};
+use crate::squid::parse_squid_with;
+use crate::w3c::W3cParser;
+
+#[derive(Error, Debug, PartialEq)]
+#[error("error on line {line_number}: {kind}")]
+pub struct LineError {
+    pub line_number: usize,
+    pub kind: ParseError,
+    pub raw: String,
+}
+
+// A `LogFormat` with any custom template compiled up front.
+enum LineFormat {
+    Common,
+    Combined,
+    VhostCombined,
+    Custom(FormatSpec),
+    W3c(W3cParser),
+    Squid,
+}
+
+impl LineFormat {
+    // `None` for W3C directive lines, which only update the parser state.
+    fn parse(&mut self, line: &str, options: &ParseOptions) -> Option<Result<LogEntry, ParseError>> {
+        match self {
+            LineFormat::Common => Some(parse_common_log_with(line, options)),
+            LineFormat::Combined => Some(parse_combined_log_with(line, options)),
+            LineFormat::VhostCombined => Some(parse_vhost_combined_log_with(line, options)),
+            LineFormat::Custom(spec) => Some(spec.parse_with(line, options)),
+            LineFormat::W3c(parser) => parser.parse_line(line),
+            LineFormat::Squid => Some(parse_squid_with(line, options)),
+        }
+    }
+}
+
+/// What `LogParser` does with a line over its length limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LongLines {
+    /// Yield `ParseError::LineTooLong` for the line.
+    #[default]
+    Reject,
+    /// Parse the first `limit` bytes, which usually fails on a missing field.
+    Truncate,
+}
+
+pub struct LogParser<R: BufRead> {
+    reader: R,
+    format: LineFormat,
+    options: ParseOptions,
+    buf: Vec<u8>,
+    max_line_length: usize,
+    long_lines: LongLines,
+    line_number: usize,
+    line_start: u64,
+    position: u64,
+}
+
+impl<R: BufRead> LogParser<R> {
+    pub fn new(reader: R) -> Self {
+        LogParser {
+            reader,
+            format: LineFormat::Common,
+            options: ParseOptions::default(),
+            buf: Vec::new(),
+            max_line_length: usize::MAX,
+            long_lines: LongLines::default(),
+            line_number: 0,
+            line_start: 0,
+            position: 0,
+        }
+    }
+
+    pub fn with_format(reader: R, format: &LogFormat) -> Result<Self, FormatError> {
+        let format = match format {
+            LogFormat::Common => LineFormat::Common,
+            LogFormat::Combined => LineFormat::Combined,
+            LogFormat::VhostCombined => LineFormat::VhostCombined,
+            LogFormat::Custom(template) => LineFormat::Custom(FormatSpec::from_apache_directive(template)?),
+            LogFormat::W3c => LineFormat::W3c(W3cParser::new()),
+            LogFormat::Squid => LineFormat::Squid,
+        };
+        Ok(LogParser {
+            format,
+            ..LogParser::new(reader)
+        })
+    }
+
+    pub fn with_options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Holds at most `limit` bytes of any line in memory, so a huge or endless line from an
+    /// untrusted source can't exhaust it. Lines are unbounded by default.
+    pub fn with_max_line_length(mut self, limit: usize, long_lines: LongLines) -> Self {
+        self.max_line_length = limit;
+        self.long_lines = long_lines;
+        self
+    }
+
+    /// Pairs each result with the byte offset of its line in the source.
+    pub fn with_offsets(self) -> WithOffsets<R> {
+        WithOffsets { parser: self }
+    }
+}
+
+impl<R: BufRead> Iterator for LogParser<R> {
+    type Item = Result<LogEntry, LineError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.line_number += 1;
+            let line_number = self.line_number;
+            let io_error = |err: io::Error| {
+                Some(Err(LineError {
+                    line_number,
+                    kind: ParseError::Io(err),
+                    raw: String::new(),
+                }))
+            };
+
+            self.line_start = self.position;
+            let (consumed, too_long) = match read_line_capped(&mut self.reader, &mut self.buf, self.max_line_length) {
+                Ok((0, _)) => return None,
+                Ok(read) => read,
+                Err(err) => return io_error(err),
+            };
+            self.position += consumed as u64;
+
+            // A cut can split a multi-byte character; drop the partial tail.
+            let line = match std::str::from_utf8(&self.buf) {
+                Ok(line) => line,
+                Err(err) if too_long && err.error_len().is_none() => {
+                    std::str::from_utf8(&self.buf[..err.valid_up_to()]).unwrap()
+                }
+                Err(_) => {
+                    return io_error(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "stream did not contain valid UTF-8",
+                    ));
+                }
+            };
+            if too_long && self.long_lines == LongLines::Reject {
+                return Some(Err(LineError {
+                    line_number,
+                    kind: ParseError::LineTooLong {
+                        limit: self.max_line_length,
+                    },
+                    raw: line.to_string(),
+                }));
+            }
+            let is_directive = matches!(self.format, LineFormat::W3c(_)) && line.starts_with('#');
+            if !self.options.report_blank_lines && !is_directive && is_blank_or_comment(line) {
+                continue;
+            }
+            let Some(result) = self.format.parse(line, &self.options) else {
+                continue;
+            };
+            return Some(result.map_err(|kind| LineError {
+                line_number,
+                kind,
+                raw: line.to_string(),
+            }));
+        }
+    }
+}
+
+// Reads one line into `buf` without its `\n` or `\r\n` ending, keeping at most `limit` bytes
+// and skipping the rest. Returns the bytes consumed, 0 at end of input, and whether the
+// line was longer than `limit`.
+fn read_line_capped(reader: &mut impl BufRead, buf: &mut Vec<u8>, limit: usize) -> io::Result<(usize, bool)> {
+    buf.clear();
+    let mut consumed = 0;
+    let mut length = 0;
+    loop {
+        let available = match reader.fill_buf() {
+            Ok(available) => available,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        if available.is_empty() {
+            break;
+        }
+        let newline = available.iter().position(|b| *b == b'\n');
+        let content = &available[..newline.unwrap_or(available.len())];
+        // One byte over the limit, so a `\r` just past it can still be recognised.
+        let room = limit.saturating_add(1).saturating_sub(buf.len());
+        buf.extend_from_slice(&content[..content.len().min(room)]);
+        length += content.len();
+
+        let used = newline.map_or(available.len(), |i| i + 1);
+        reader.consume(used);
+        consumed += used;
+        if newline.is_some() {
+            break;
+        }
+    }
+
+    if buf.len() == length && buf.last() == Some(&b'\r') {
+        buf.pop();
+        length -= 1;
+    }
+    let too_long = length > limit;
+    buf.truncate(limit);
+    Ok((consumed, too_long))
+}
+
+// Blank lines and `#` comments show up in concatenated files and W3C-style headers.
+pub(crate) fn is_blank_or_comment(line: &str) -> bool {
+    let line = line.trim_start();
+    line.is_empty() || line.starts_with('#')
+}
+
+pub struct WithOffsets<R: BufRead> {
+    parser: LogParser<R>,
+}
+
+impl<R: BufRead> Iterator for WithOffsets<R> {
+    type Item = (u64, Result<LogEntry, ParseError>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let result = self.parser.next()?;
+        Some((self.parser.line_start, result.map_err(|err| err.kind)))
+    }
+}
+
+impl<R: BufRead + Seek> LogParser<R> {
+    /// Yields the entries of a time-sorted log that fall in `range`. Instead of scanning from
+    /// the start, it binary-searches the byte offsets for the first entry at or after
+    /// `range.since` and stops after the last entry before `range.until`.
+    ///
+    /// Input that can't seek, such as a pipe, and W3C logs, whose `#Fields:` directive has to
+    /// be read first, are scanned linearly instead. Line numbers in errors count from the point
+    /// the search seeked to.
+    pub fn entries_between(mut self, range: TimeRange) -> io::Result<EntriesBetween<R>> {
+        if let Some(since) = range.since
+            && !matches!(self.format, LineFormat::W3c(_))
+            && let Some(start) = self.seek_to(since)?
+        {
+            self.reader.seek(SeekFrom::Start(start))?;
+            self.position = start;
+            self.line_number = 0;
+        }
+        Ok(EntriesBetween { parser: self, range })
+    }
+
+    // Finds the start of the first line whose entry is at or after `since`, or `None` if the
+    // reader can't seek. Unparseable lines are skipped over while probing.
+    fn seek_to(&mut self, since: DateTime<Utc>) -> io::Result<Option<u64>> {
+        let Ok(start) = self.reader.stream_position() else {
+            return Ok(None);
+        };
+        let Ok(end) = self.reader.seek(SeekFrom::End(0)) else {
+            return Ok(None);
+        };
+
+        // Every entry starting before `low` is earlier than `since`, and the first entry
+        // starting at or after `high` is not.
+        let (mut low, mut high) = (start, end);
+        while low < high {
+            let middle = low + (high - low) / 2;
+            match self.probe(start, middle)? {
+                Some((timestamp, line_end)) if timestamp < since => low = line_end,
+                _ => high = middle,
+            }
+        }
+        Ok(Some(low))
+    }
+
+    // Parses the first entry on a line starting at or after `offset`, returning its timestamp
+    // and the offset just past its line.
+    fn probe(&mut self, start: u64, offset: u64) -> io::Result<Option<(DateTime<Utc>, u64)>> {
+        self.position = if offset > start {
+            // Step back a byte so a line starting exactly at `offset` isn't skipped.
+            self.reader.seek(SeekFrom::Start(offset - 1))?;
+            let (consumed, _) = read_line_capped(&mut self.reader, &mut self.buf, 0)?;
+            offset - 1 + consumed as u64
+        } else {
+            self.reader.seek(SeekFrom::Start(start))?
+        };
+
+        while let Some(result) = self.next() {
+            match result {
+                Ok(entry) => return Ok(Some((entry.timestamp.to_utc(), self.position))),
+                Err(LineError {
+                    kind: ParseError::Io(err),
+                    ..
+                }) => return Err(err),
+                Err(_) => {}
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// The entries of a sorted log within a time range, see `LogParser::entries_between`.
+pub struct EntriesBetween<R: BufRead> {
+    parser: LogParser<R>,
+    range: TimeRange,
+}
+
+impl<R: BufRead> Iterator for EntriesBetween<R> {
+    type Item = Result<LogEntry, LineError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.parser.next()? {
+                Ok(entry) if self.range.since.is_some_and(|since| entry.timestamp < since) => continue,
+                // The log is sorted, so nothing later can be in range.
+                Ok(entry) if self.range.until.is_some_and(|until| entry.timestamp > until) => return None,
+                result => return Some(result),
+            }
+        }
+    }
+}
+
+// Bytes needed to tell every `Compression` apart; zstd's magic number is the longest.
+const MAGIC_LEN: usize = 4;
+
+// The first read in `decompress`, matching `BufReader`'s default capacity.
+const HEADER_LEN: usize = 8 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Compression {
+    None,
+    Gzip,
+    Zstd,
+    Bzip2,
+}
+
+impl Compression {
+    // Magic bytes win over the extension, so misnamed files still decode.
+    fn detect(header: &[u8], path: Option<&Path>) -> Compression {
+        if header.starts_with(&[0x1f, 0x8b]) {
+            return Compression::Gzip;
+        }
+        if header.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            return Compression::Zstd;
+        }
+        if header.starts_with(b"BZh") {
+            return Compression::Bzip2;
+        }
+        match path.and_then(|path| path.extension()).and_then(|ext| ext.to_str()) {
+            Some("gz") => Compression::Gzip,
+            Some("zst") => Compression::Zstd,
+            Some("bz2") => Compression::Bzip2,
+            _ => Compression::None,
+        }
+    }
+}
+
+#[cfg(not(all(feature = "zstd", feature = "bzip2")))]
+fn unsupported(feature: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("{feature} input needs the `{feature}` feature"),
+    )
+}
+
+/// Opens a log file, decompressing gzip and, with the `zstd` and `bzip2` features, zstd and
+/// bzip2 input. The compression is detected from the magic bytes, falling back to
+/// `.gz`/`.zst`/`.bz2` extensions.
+pub fn open_maybe_compressed(path: impl AsRef<Path>) -> io::Result<Box<dyn BufRead>> {
+    let path = path.as_ref();
+    decompress(File::open(path)?, Some(path))
+}
+
+/// Whether `decompress` would wrap `reader` in a decoder, going by its leading bytes and the
+/// `path` extension.
+pub fn is_compressed(reader: &mut impl BufRead, path: Option<&Path>) -> io::Result<bool> {
+    Ok(Compression::detect(reader.fill_buf()?, path) != Compression::None)
+}
+
+/// Wraps `reader` in the decoder its leading bytes call for, like `open_maybe_compressed`;
+/// `path` is only used as an extension hint, so unnamed input such as stdin is detected by
+/// its magic bytes alone.
+pub fn decompress(mut reader: impl Read + 'static, path: Option<&Path>) -> io::Result<Box<dyn BufRead>> {
+    // A pipe can hand over fewer bytes per read than the longest magic number, so keep reading
+    // until there are enough, then chain them back in front of the rest. A whole block is read,
+    // as `BufReader` would, so the first `fill_buf` still shows enough lines to detect a format.
+    let mut header = vec![0; HEADER_LEN];
+    let mut filled = 0;
+    while filled < MAGIC_LEN {
+        match reader.read(&mut header[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+    }
+    header.truncate(filled);
+    let compression = Compression::detect(&header, path);
+    let reader = BufReader::new(io::Cursor::new(header).chain(reader));
+
+    Ok(match compression {
+        Compression::None => Box::new(reader),
+        // Rotated logs are usually gzipped, sometimes as several concatenated members.
+        Compression::Gzip => Box::new(BufReader::new(MultiGzDecoder::new(reader))),
+        #[cfg(feature = "zstd")]
+        Compression::Zstd => Box::new(BufReader::new(zstd::stream::read::Decoder::with_buffer(reader)?)),
+        #[cfg(not(feature = "zstd"))]
+        Compression::Zstd => return Err(unsupported("zstd")),
+        #[cfg(feature = "bzip2")]
+        Compression::Bzip2 => Box::new(BufReader::new(bzip2::bufread::MultiBzDecoder::new(reader))),
+        #[cfg(not(feature = "bzip2"))]
+        Compression::Bzip2 => return Err(unsupported("bzip2")),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_parser_iterates_lines() {
+        let input = "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"POST /api/login HTTP/1.1\" 201 567\n\
+                     not a log line\n\
+                     8.8.8.8 - - [15/Jan/2024:10:29:47 +0000] \"DELETE /users HTTP/1.1\" 403 89";
+
+        let results = LogParser::new(input.as_bytes()).collect::<Vec<_>>();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].as_ref().unwrap().path, "/api/login");
+        assert_eq!(
+            results[1].as_ref().unwrap_err(),
+            &LineError {
+                line_number: 2,
+                kind: ParseError::InvalidFormat,
+                raw: "not a log line".to_string(),
+            }
+        );
+        assert_eq!(
+            results[1].as_ref().unwrap_err().to_string(),
+            "error on line 2: invalid log format"
+        );
+        assert_eq!(results[2].as_ref().unwrap().size, 89);
+
+        let errors = LogParser::new(input.as_bytes()).filter(Result::is_err).count();
+        assert_eq!(errors, 1);
+    }
+
+    #[test]
+    fn test_log_parser_with_format() {
+        let input = "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"POST /api/login HTTP/1.1\" 201 567 \"-\" \"curl/8.0\"\n";
+
+        let entry = LogParser::with_format(input.as_bytes(), &LogFormat::Combined)
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(entry.user_agent, Some("curl/8.0".to_string()));
+
+        let custom = LogFormat::Custom("%h %l %u %t \"%r\" %>s %b %{User-agent}i".to_string());
+        assert!(LogParser::with_format(input.as_bytes(), &custom).is_ok());
+
+        let invalid = LogFormat::Custom("%h %Q".to_string());
+        assert!(matches!(
+            LogParser::with_format(input.as_bytes(), &invalid),
+            Err(FormatError::UnknownDirective(_))
+        ));
+    }
+
+    #[test]
+    fn test_with_offsets() {
+        let first = "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"POST /api/login HTTP/1.1\" 201 567\r\n";
+        let second = "not a log line\n";
+        let third = "8.8.8.8 - - [15/Jan/2024:10:29:47 +0000] \"DELETE /users HTTP/1.1\" 403 89";
+        let input = format!("{first}{second}{third}");
+
+        let results = LogParser::new(input.as_bytes()).with_offsets().collect::<Vec<_>>();
+        let offsets = results.iter().map(|(offset, _)| *offset).collect::<Vec<_>>();
+        assert_eq!(offsets, [0, first.len() as u64, (first.len() + second.len()) as u64]);
+        assert_eq!(results[1].1, Err(ParseError::InvalidFormat));
+        assert_eq!(results[2].1.as_ref().unwrap().path, "/users");
+        assert!(input[offsets[2] as usize..].starts_with("8.8.8.8"));
+    }
+
+    #[test]
+    fn test_skips_blank_and_comment_lines() {
+        let input = "#Software: Apache\n\
+                     \n\
+                     10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"POST /api/login HTTP/1.1\" 201 567\n   \t\r\n\
+                     # rotated here\n\
+                     8.8.8.8 - - [15/Jan/2024:10:29:47 +0000] \"DELETE /users HTTP/1.1\" 403 89\n";
+
+        let results = LogParser::new(input.as_bytes()).collect::<Vec<_>>();
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(Result::is_ok));
+
+        let offsets = LogParser::new(input.as_bytes()).with_offsets().map(|(offset, _)| offset);
+        for offset in offsets {
+            assert!(input[offset as usize..].starts_with(['1', '8']));
+        }
+
+        let options = ParseOptions {
+            report_blank_lines: true,
+            ..Default::default()
+        };
+        let errors = LogParser::new(input.as_bytes())
+            .with_options(options)
+            .filter_map(Result::err)
+            .map(|err| err.line_number)
+            .collect::<Vec<_>>();
+        assert_eq!(errors, [1, 2, 4, 5]);
+    }
+
+    #[test]
+    fn test_detect_compression() {
+        let cases: [(&[u8], Option<&str>, Compression); 7] = [
+            (b"\x1f\x8b\x08\x00", None, Compression::Gzip),
+            (b"\x28\xb5\x2f\xfd", Some("access.log"), Compression::Zstd),
+            (b"BZh91AY", None, Compression::Bzip2),
+            (b"10.0.0.5 - -", Some("access.log"), Compression::None),
+            (b"", Some("access.log.gz"), Compression::Gzip),
+            (b"", Some("access.log.zst"), Compression::Zstd),
+            (b"", Some("access.log.bz2"), Compression::Bzip2),
+        ];
+
+        for (header, path, expected) in cases {
+            assert_eq!(Compression::detect(header, path.map(Path::new)), expected, "Wrong result for {path:?}");
+        }
+
+        assert!(is_compressed(&mut &b"\x1f\x8b\x08\x00"[..], None).unwrap());
+        assert!(!is_compressed(&mut &b"10.0.0.5 - -"[..], Some(Path::new("access.log"))).unwrap());
+
+        let plain = decompress(&b"line\n"[..], None).unwrap();
+        assert_eq!(plain.lines().map(Result::unwrap).collect::<Vec<_>>(), ["line"]);
+        let short = decompress(&b"x"[..], None).unwrap();
+        assert_eq!(short.lines().map(Result::unwrap).collect::<Vec<_>>(), ["x"]);
+
+        // Format detection peeks at whatever the first `fill_buf` returns.
+        let mut lines = decompress(&b"first line\nsecond line\n"[..], None).unwrap();
+        assert_eq!(lines.fill_buf().unwrap(), b"first line\nsecond line\n");
+    }
+
+    // Hands out one byte per read, like a slow pipe.
+    struct Trickle(io::Cursor<Vec<u8>>);
+
+    impl Read for Trickle {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = buf.len().min(1);
+            self.0.read(&mut buf[..len])
+        }
+    }
+
+    #[test]
+    fn test_decompress_unnamed_gzip_stream() {
+        use flate2::write::GzEncoder;
+        use std::io::Write;
+
+        let line = "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"GET /api HTTP/1.1\" 200 567";
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        writeln!(encoder, "{line}").unwrap();
+        let gzipped = encoder.finish().unwrap();
+
+        let reader = decompress(Trickle(io::Cursor::new(gzipped)), None).unwrap();
+        let entries = LogParser::new(reader).collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].path, "/api");
+    }
+
+    #[test]
+    fn test_max_line_length() {
+        let line = "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"GET /api HTTP/1.1\" 200 567";
+        let long = format!("10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"GET /{} HTTP/1.1\" 200 567", "a".repeat(100));
+        let input = format!("{line}\r\n{long}\n{line}");
+
+        let parse = |long_lines| {
+            LogParser::new(input.as_bytes())
+                .with_max_line_length(line.len(), long_lines)
+                .with_offsets()
+                .collect::<Vec<_>>()
+        };
+
+        let results = parse(LongLines::Reject);
+        assert_eq!(results.len(), 3);
+        assert!(results[0].1.is_ok(), "CRLF ending must not count towards the limit");
+        assert_eq!(results[1].0, line.len() as u64 + 2);
+        assert_eq!(results[1].1, Err(ParseError::LineTooLong { limit: line.len() }));
+        assert_eq!(results[2].0, (line.len() + long.len() + 3) as u64);
+        assert!(results[2].1.is_ok());
+
+        let results = parse(LongLines::Truncate);
+        assert_eq!(results[1].1, Err(ParseError::TooFewFields { expected: 7, found: 4 }));
+        assert!(results[2].1.is_ok());
+
+        let raw = LogParser::new("héllo wörld\n".as_bytes())
+            .with_max_line_length(2, LongLines::Reject)
+            .map(|result| result.unwrap_err().raw)
+            .collect::<Vec<_>>();
+        assert_eq!(raw, ["h"]);
+    }
+
+    #[test]
+    fn test_log_parser_w3c() {
+        let input = "#Version: 1.0\n\
+                     #Fields: date time c-ip cs-method cs-uri-stem sc-status sc-bytes\n\
+                     2024-01-15 10:24:12 10.0.0.5 GET /api 200 1234\n\
+                     \n\
+                     2024-01-15 10:24:13 10.0.0.6 POST /login 401 89\n";
+
+        let entries = LogParser::with_format(input.as_bytes(), &LogFormat::W3c)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].path, "/login");
+        assert_eq!(entries[1].status, 401);
+    }
+
+    #[test]
+    fn test_entries_between() {
+        use chrono::TimeZone;
+        use std::io::Cursor;
+
+        // Minute n logs /n; every fifth minute is logged twice and every seventh line is junk.
+        let mut input = String::new();
+        for minute in 0..50 {
+            let repeats = if minute % 5 == 0 { 2 } else { 1 };
+            for _ in 0..repeats {
+                input.push_str(&format!(
+                    "10.0.0.5 - - [15/Jan/2024:10:{minute:02}:00 +0000] \"GET /{minute} HTTP/1.1\" 200 5\n"
+                ));
+            }
+            if minute % 7 == 0 {
+                input.push_str("not a log line\n");
+            }
+        }
+        let at = |minute| Some(Utc.with_ymd_and_hms(2024, 1, 15, 10, minute, 0).unwrap());
+        let paths = |range: TimeRange| {
+            LogParser::new(Cursor::new(input.as_bytes()))
+                .entries_between(range)
+                .unwrap()
+                .filter_map(Result::ok)
+                .map(|entry| entry.path)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(paths(TimeRange::new(at(10), at(12))), ["/10", "/10", "/11", "/12"]);
+        assert_eq!(paths(TimeRange::new(at(0), at(0))), ["/0", "/0"]);
+        assert_eq!(paths(TimeRange::new(at(49), None)), ["/49"]);
+        assert_eq!(paths(TimeRange::new(at(50), None)), Vec::<String>::new());
+        assert_eq!(paths(TimeRange::new(None, at(1))), ["/0", "/0", "/1"]);
+        for since in 0..50 {
+            let found = paths(TimeRange::new(at(since), None));
+            assert_eq!(found.first(), Some(&format!("/{since}")), "Wrong start for minute {since}");
+        }
+
+        // Errors after the seek point still come through.
+        let results = LogParser::new(Cursor::new(input.as_bytes()))
+            .entries_between(TimeRange::new(at(6), at(8)))
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[2].as_ref().unwrap_err().kind, ParseError::InvalidFormat);
+
+        // A read error while probing comes back as it was raised.
+        let mut input = input.into_bytes();
+        input.extend_from_slice(b"\xff\xfe not utf-8\n".repeat(50).as_slice());
+        let err = LogParser::new(Cursor::new(input))
+            .entries_between(TimeRange::new(at(49), None))
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_entries_between_without_seeking() {
+        use chrono::TimeZone;
+
+        // A pipe: reads work, seeks fail.
+        struct Pipe<'a>(&'a [u8]);
+        impl Read for Pipe<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                self.0.read(buf)
+            }
+        }
+        impl Seek for Pipe<'_> {
+            fn seek(&mut self, _: SeekFrom) -> io::Result<u64> {
+                Err(io::Error::from(io::ErrorKind::Unsupported))
+            }
+        }
+
+        let input = "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"GET /early HTTP/1.1\" 200 5\n\
+                     10.0.0.5 - - [15/Jan/2024:10:25:12 +0000] \"GET /late HTTP/1.1\" 200 5\n";
+        let since = Utc.with_ymd_and_hms(2024, 1, 15, 10, 25, 0).unwrap();
+        let entries = LogParser::new(BufReader::new(Pipe(input.as_bytes())))
+            .entries_between(TimeRange::new(Some(since), None))
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].path, "/late");
+    }
+}
@@ -0,0 +1,819 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::net::IpAddr;
+
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+use regex::Regex;
+
+use crate::parser::{LogEntry, SizeUnits, format_size};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StatusHistogram {
+    counts: BTreeMap<u16, usize>,
+}
+
+impl StatusHistogram {
+    pub fn new() -> Self {
+        StatusHistogram::default()
+    }
+
+    pub fn add(&mut self, entry: &LogEntry) {
+        *self.counts.entry(entry.status).or_default() += 1;
+    }
+
+    pub fn count(&self, status: u16) -> usize {
+        self.counts.get(&status).copied().unwrap_or(0)
+    }
+
+    pub fn total(&self) -> usize {
+        self.counts.values().sum()
+    }
+}
+
+// 200: 10423
+// 404: 88
+impl fmt::Display for StatusHistogram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (status, count) in &self.counts {
+            writeln!(f, "{}: {}", status, count)?;
+        }
+        Ok(())
+    }
+}
+
+/// The `n` most requested paths, most frequent first; ties are ordered by path.
+pub fn top_paths<'a>(entries: impl IntoIterator<Item = &'a LogEntry>, n: usize) -> Vec<(String, usize)> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for entry in entries {
+        *counts.entry(&entry.path).or_default() += 1;
+    }
+
+    let mut ranked = counts
+        .into_iter()
+        .map(|(path, count)| (path.to_string(), count))
+        .collect::<Vec<_>>();
+    ranked.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    ranked.truncate(n);
+    ranked
+}
+
+/// Rewrites whole path segments matching `pattern` to `placeholder` in `normalize_path`.
+#[derive(Debug, Clone)]
+pub struct PathRule {
+    pattern: Regex,
+    placeholder: String,
+}
+
+impl PathRule {
+    /// `pattern` must match the entire segment, so `[0-9]+` leaves `v2` alone.
+    pub fn new(pattern: &str, placeholder: impl Into<String>) -> Result<Self, regex::Error> {
+        Ok(PathRule {
+            pattern: Regex::new(&format!("^(?:{pattern})$"))?,
+            placeholder: placeholder.into(),
+        })
+    }
+
+    /// UUIDs become `{uuid}`, all-digit segments `{id}` and hex strings of 16 or more
+    /// digits, such as hashes, `{hash}`.
+    pub fn defaults() -> Vec<PathRule> {
+        [
+            ("[[:xdigit:]]{8}-[[:xdigit:]]{4}-[[:xdigit:]]{4}-[[:xdigit:]]{4}-[[:xdigit:]]{12}", "{uuid}"),
+            ("[0-9]+", "{id}"),
+            ("[[:xdigit:]]{16,}", "{hash}"),
+        ]
+        .into_iter()
+        .map(|(pattern, placeholder)| PathRule::new(pattern, placeholder).unwrap())
+        .collect()
+    }
+}
+
+/// Replaces each segment of `path` matching one of `rules` with that rule's placeholder, the
+/// first matching rule winning, so `/users/42/orders` becomes `/users/{id}/orders` with
+/// `PathRule::defaults`. Lets reports group high-cardinality paths.
+pub fn normalize_path(path: &str, rules: &[PathRule]) -> String {
+    path.split('/')
+        .map(|segment| {
+            rules
+                .iter()
+                .find(|rule| !segment.is_empty() && rule.pattern.is_match(segment))
+                .map_or(segment, |rule| rule.placeholder.as_str())
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Like `top_paths`, counting each path by its `normalize_path` form.
+pub fn top_normalized_paths<'a>(
+    entries: impl IntoIterator<Item = &'a LogEntry>,
+    n: usize,
+    rules: &[PathRule],
+) -> Vec<(String, usize)> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for entry in entries {
+        *counts.entry(normalize_path(&entry.path, rules)).or_default() += 1;
+    }
+
+    let mut ranked = counts.into_iter().collect::<Vec<_>>();
+    ranked.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    ranked.truncate(n);
+    ranked
+}
+
+/// Hit counts by the first `depth` path segments, e.g. `/api/users` for depth 2, most
+/// frequent first; ties are ordered by prefix. Shorter paths count under their full path.
+pub fn hits_by_prefix<'a>(entries: impl IntoIterator<Item = &'a LogEntry>, depth: usize) -> Vec<(String, usize)> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for entry in entries {
+        let segments = entry.path_segments();
+        let prefix = format!("/{}", segments[..segments.len().min(depth)].join("/"));
+        *counts.entry(prefix).or_default() += 1;
+    }
+
+    let mut ranked = counts.into_iter().collect::<Vec<_>>();
+    ranked.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    ranked
+}
+
+/// Hit counts by `LogEntry::referrer_host`, most frequent first; ties are ordered by host.
+/// Entries without a usable referrer count under `None`.
+pub fn hits_by_referrer_host<'a>(entries: impl IntoIterator<Item = &'a LogEntry>) -> Vec<(Option<String>, usize)> {
+    let mut counts: HashMap<Option<String>, usize> = HashMap::new();
+    for entry in entries {
+        *counts.entry(entry.referrer_host()).or_default() += 1;
+    }
+
+    let mut ranked = counts.into_iter().collect::<Vec<_>>();
+    ranked.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    ranked
+}
+
+/// Bytes sent to each client. Sizes come from untrusted log lines, so totals saturate at
+/// `u64::MAX` rather than overflow.
+pub fn bytes_by_ip<'a>(entries: impl IntoIterator<Item = &'a LogEntry>) -> HashMap<IpAddr, u64> {
+    let mut totals: HashMap<IpAddr, u64> = HashMap::new();
+    for entry in entries {
+        let total = totals.entry(entry.ip).or_default();
+        *total = total.saturating_add(entry.size);
+    }
+    totals
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ClientTraffic {
+    pub bytes: u64,
+    pub requests: usize,
+}
+
+/// The `n` clients that transferred the most bytes; ties are ordered by address.
+pub fn top_ips<'a>(entries: impl IntoIterator<Item = &'a LogEntry>, n: usize) -> Vec<(IpAddr, ClientTraffic)> {
+    let entries = entries.into_iter().collect::<Vec<_>>();
+    let mut requests: HashMap<IpAddr, usize> = HashMap::new();
+    for entry in &entries {
+        *requests.entry(entry.ip).or_default() += 1;
+    }
+
+    let mut ranked = bytes_by_ip(entries)
+        .into_iter()
+        .map(|(ip, bytes)| (ip, ClientTraffic { bytes, requests: requests[&ip] }))
+        .collect::<Vec<_>>();
+    ranked.sort_by(|a, b| b.1.bytes.cmp(&a.1.bytes).then_with(|| a.0.cmp(&b.0)));
+    ranked.truncate(n);
+    ranked
+}
+
+/// Response size distribution; collect it from entries with `FromIterator`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SizeStats {
+    sorted: Vec<u64>,
+}
+
+impl SizeStats {
+    pub fn from_sizes(sizes: impl IntoIterator<Item = u64>) -> Self {
+        let mut sorted = sizes.into_iter().collect::<Vec<_>>();
+        sorted.sort_unstable();
+        SizeStats { sorted }
+    }
+
+    pub fn count(&self) -> usize {
+        self.sorted.len()
+    }
+
+    pub fn total(&self) -> u64 {
+        self.sorted.iter().fold(0, |total, size| total.saturating_add(*size))
+    }
+
+    /// Zero when there are no entries.
+    pub fn mean(&self) -> f64 {
+        if self.sorted.is_empty() {
+            return 0.0;
+        }
+        self.sorted.iter().map(|size| *size as f64).sum::<f64>() / self.sorted.len() as f64
+    }
+
+    /// Nearest-rank percentile, so the result is always an observed size; zero when there
+    /// are no entries. `p` is clamped to `0.0..=100.0`.
+    pub fn percentile(&self, p: f64) -> u64 {
+        if self.sorted.is_empty() {
+            return 0;
+        }
+        let rank = (p.clamp(0.0, 100.0) / 100.0 * self.sorted.len() as f64).ceil() as usize;
+        self.sorted[rank.saturating_sub(1)]
+    }
+}
+
+impl<'a> FromIterator<&'a LogEntry> for SizeStats {
+    fn from_iter<I: IntoIterator<Item = &'a LogEntry>>(entries: I) -> Self {
+        SizeStats::from_sizes(entries.into_iter().map(|entry| entry.size))
+    }
+}
+
+fn human(bytes: u64) -> String {
+    format_size(bytes, SizeUnits::Binary)
+}
+
+// total: 1.2 GiB
+// average: 2.0 KiB
+// p50: 512 B
+impl fmt::Display for SizeStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "total: {}", human(self.total()))?;
+        writeln!(f, "average: {}", human(self.mean().round() as u64))?;
+        for p in [50, 95, 99] {
+            writeln!(f, "p{}: {}", p, human(self.percentile(p as f64)))?;
+        }
+        Ok(())
+    }
+}
+
+// Each `ApproxQuantiles` estimate is within this fraction of an exact quantile.
+const RELATIVE_ACCURACY: f64 = 0.01;
+
+/// Response size quantiles estimated in bounded memory, for streams too long to keep every
+/// size as `SizeStats` does. Sizes are counted in logarithmic buckets, as in DDSketch, so
+/// any `u64` stream fits in about 2,200 buckets and each quantile is within 1% of the exact
+/// nearest-rank value.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ApproxQuantiles {
+    // Bucket `i` counts sizes in `(γ^(i-1), γ^i]`; zero has no logarithm so is counted apart.
+    buckets: BTreeMap<u32, u64>,
+    zeros: u64,
+    count: u64,
+    total: u64,
+}
+
+impl ApproxQuantiles {
+    pub fn new() -> Self {
+        ApproxQuantiles::default()
+    }
+
+    fn gamma() -> f64 {
+        (1.0 + RELATIVE_ACCURACY) / (1.0 - RELATIVE_ACCURACY)
+    }
+
+    pub fn add(&mut self, size: u64) {
+        self.count += 1;
+        self.total = self.total.saturating_add(size);
+        if size == 0 {
+            self.zeros += 1;
+            return;
+        }
+        let bucket = ((size as f64).ln() / Self::gamma().ln()).ceil() as u32;
+        *self.buckets.entry(bucket).or_default() += 1;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Zero when nothing was added.
+    pub fn mean(&self) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        self.total as f64 / self.count as f64
+    }
+
+    /// The estimated nearest-rank quantile, e.g. `0.95` for p95; zero when nothing was added.
+    /// `q` is clamped to `0.0..=1.0`.
+    pub fn quantile(&self, q: f64) -> u64 {
+        let rank = ((q.clamp(0.0, 1.0) * self.count as f64).ceil() as u64).max(1);
+        if rank <= self.zeros {
+            return 0;
+        }
+        let mut seen = self.zeros;
+        for (bucket, count) in &self.buckets {
+            seen += count;
+            if seen >= rank {
+                // The point of the bucket within `RELATIVE_ACCURACY` of both its bounds.
+                let gamma = Self::gamma();
+                return (2.0 * gamma.powi(*bucket as i32) / (gamma + 1.0)).round() as u64;
+            }
+        }
+        0
+    }
+}
+
+impl<'a> FromIterator<&'a LogEntry> for ApproxQuantiles {
+    fn from_iter<I: IntoIterator<Item = &'a LogEntry>>(entries: I) -> Self {
+        let mut quantiles = ApproxQuantiles::new();
+        for entry in entries {
+            quantiles.add(entry.size);
+        }
+        quantiles
+    }
+}
+
+// Like `SizeStats`, with the estimates marked: `p50: ~512 B`.
+impl fmt::Display for ApproxQuantiles {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "total: {}", human(self.total()))?;
+        writeln!(f, "average: {}", human(self.mean().round() as u64))?;
+        for p in [50, 95, 99] {
+            writeln!(f, "p{}: ~{}", p, human(self.quantile(p as f64 / 100.0)))?;
+        }
+        Ok(())
+    }
+}
+
+/// 404 counts per missing path and referrer, to find the pages linking to it; `None` collects
+/// requests that sent no referrer.
+pub fn group_404s_by_referrer<'a>(
+    entries: impl IntoIterator<Item = &'a LogEntry>,
+) -> BTreeMap<String, BTreeMap<Option<String>, usize>> {
+    let mut groups: BTreeMap<String, BTreeMap<Option<String>, usize>> = BTreeMap::new();
+    for entry in entries.into_iter().filter(|entry| entry.status == 404) {
+        let referrers = groups.entry(entry.path.clone()).or_default();
+        *referrers.entry(entry.referrer.clone()).or_default() += 1;
+    }
+    groups
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    Timestamp,
+    Status,
+    Size,
+    Ip,
+}
+
+impl SortKey {
+    /// A comparator for `sort_by`; with `reverse`, entries with equal keys still keep
+    /// their input order under a stable sort.
+    pub fn comparator(self, reverse: bool) -> impl Fn(&LogEntry, &LogEntry) -> Ordering {
+        move |a, b| {
+            let ordering = match self {
+                SortKey::Timestamp => a.timestamp.cmp(&b.timestamp),
+                SortKey::Status => a.status.cmp(&b.status),
+                SortKey::Size => a.size.cmp(&b.size),
+                SortKey::Ip => a.ip.cmp(&b.ip),
+            };
+            if reverse { ordering.reverse() } else { ordering }
+        }
+    }
+}
+
+/// Stable sort; entries with equal keys keep their input order.
+pub fn sort_entries(entries: &mut [LogEntry], key: SortKey, reverse: bool) {
+    entries.sort_by(key.comparator(reverse));
+}
+
+/// Counts entries per `interval`, keyed by the UTC start of each bucket. Buckets with no
+/// entries are absent.
+///
+/// Panics if `interval` is not positive.
+pub fn bucket_by_interval<'a>(
+    entries: impl IntoIterator<Item = &'a LogEntry>,
+    interval: TimeDelta,
+) -> BTreeMap<DateTime<Utc>, usize> {
+    let step = interval.num_milliseconds();
+    assert!(step > 0, "bucket interval must be positive");
+
+    let mut buckets = BTreeMap::new();
+    for entry in entries {
+        let millis = entry.timestamp.timestamp_millis();
+        let start = DateTime::from_timestamp_millis(millis - millis.rem_euclid(step)).unwrap();
+        *buckets.entry(start).or_default() += 1;
+    }
+    buckets
+}
+
+/// Flags clients that sent more than `threshold` requests within any `window`, returning each
+/// client with the time of the request that crossed the threshold, earliest first. A client
+/// is flagged again only after its rate has dropped back to the threshold.
+///
+/// Each client's entries must be in time order; a sliding window of their timestamps is kept
+/// per client.
+pub fn detect_rate_spikes<'a>(
+    entries: impl IntoIterator<Item = &'a LogEntry>,
+    window: TimeDelta,
+    threshold: usize,
+) -> Vec<(IpAddr, DateTime<Utc>)> {
+    // Timestamps inside the current window, and whether the client is already flagged.
+    let mut clients: HashMap<IpAddr, (VecDeque<DateTime<Utc>>, bool)> = HashMap::new();
+    let mut spikes = Vec::new();
+    for entry in entries {
+        let timestamp = entry.timestamp.to_utc();
+        let (recent, flagged) = clients.entry(entry.ip).or_default();
+        recent.push_back(timestamp);
+        while recent.front().is_some_and(|first| timestamp - *first >= window) {
+            recent.pop_front();
+        }
+
+        if recent.len() <= threshold {
+            *flagged = false;
+        } else if !*flagged {
+            *flagged = true;
+            spikes.push((entry.ip, timestamp));
+        }
+    }
+    spikes.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+    spikes
+}
+
+/// The `n` entries that took longest to serve, slowest first; entries without a duration
+/// are left out and ties keep their input order.
+pub fn slowest<'a>(entries: impl IntoIterator<Item = &'a LogEntry>, n: usize) -> Vec<&'a LogEntry> {
+    let mut timed = entries
+        .into_iter()
+        .filter(|entry| entry.duration.is_some())
+        .collect::<Vec<_>>();
+    timed.sort_by_key(|entry| Reverse(entry.duration));
+    timed.truncate(n);
+    timed
+}
+
+/// Distinct client addresses per day, keyed by the UTC date of each timestamp so entries
+/// logged with different offsets land in the same day.
+pub fn unique_ips_per_day<'a>(entries: impl IntoIterator<Item = &'a LogEntry>) -> BTreeMap<NaiveDate, usize> {
+    let mut visitors: BTreeMap<NaiveDate, HashSet<IpAddr>> = BTreeMap::new();
+    for entry in entries {
+        visitors.entry(entry.timestamp.to_utc().date_naive()).or_default().insert(entry.ip);
+    }
+
+    visitors.into_iter().map(|(day, ips)| (day, ips.len())).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_status_histogram() {
+        let mut histogram = StatusHistogram::new();
+        for status in [200, 404, 200, 500, 200, 404] {
+            histogram.add(&LogEntry::builder().status(status).build());
+        }
+
+        assert_eq!(histogram.count(200), 3);
+        assert_eq!(histogram.count(404), 2);
+        assert_eq!(histogram.count(301), 0);
+        assert_eq!(histogram.total(), 6);
+        assert_eq!(histogram.to_string(), "200: 3\n404: 2\n500: 1\n");
+        assert_eq!(StatusHistogram::new().to_string(), "");
+    }
+
+    #[test]
+    fn test_size_stats() {
+        let stats = SizeStats::from_sizes((1..=100).rev());
+        assert_eq!(stats.count(), 100);
+        assert_eq!(stats.total(), 5050);
+        assert_eq!(stats.mean(), 50.5);
+        assert_eq!(stats.percentile(50.0), 50);
+        assert_eq!(stats.percentile(95.0), 95);
+        assert_eq!(stats.percentile(99.0), 99);
+        assert_eq!(stats.percentile(0.0), 1);
+        assert_eq!(stats.percentile(100.0), 100);
+
+        let entries = [10, 1000, 30].map(|size| LogEntry {
+            size,
+            ..Default::default()
+        });
+        let stats = entries.iter().collect::<SizeStats>();
+        assert_eq!(stats.percentile(50.0), 30);
+        assert_eq!(stats.percentile(99.0), 1000);
+        assert_eq!(
+            stats.to_string(),
+            "total: 1.0 KiB\naverage: 347 B\np50: 30 B\np95: 1000 B\np99: 1000 B\n"
+        );
+
+        let empty = SizeStats::default();
+        assert_eq!((empty.total(), empty.mean(), empty.percentile(95.0)), (0, 0.0, 0));
+        assert_eq!(
+            empty.to_string(),
+            "total: 0 B\naverage: 0 B\np50: 0 B\np95: 0 B\np99: 0 B\n"
+        );
+    }
+
+    #[test]
+    fn test_group_404s_by_referrer() {
+        let entries = [
+            (404, "/old.png", Some("https://example.com/a")),
+            (404, "/old.png", Some("https://example.com/a")),
+            (404, "/old.png", None),
+            (200, "/old.png", Some("https://example.com/b")),
+            (404, "/gone", Some("https://example.com/b")),
+            (410, "/gone", Some("https://example.com/c")),
+        ]
+        .map(|(status, path, referrer)| LogEntry {
+            status,
+            path: path.to_string(),
+            referrer: referrer.map(str::to_string),
+            ..Default::default()
+        });
+
+        let groups = group_404s_by_referrer(&entries);
+        assert_eq!(groups.keys().collect::<Vec<_>>(), ["/gone", "/old.png"]);
+        assert_eq!(
+            groups["/old.png"].iter().collect::<Vec<_>>(),
+            [(&None, &1), (&Some("https://example.com/a".to_string()), &2)]
+        );
+        assert_eq!(groups["/gone"].len(), 1);
+        assert!(group_404s_by_referrer(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_top_paths() {
+        let entries = ["/b", "/a", "/c", "/a", "/b", "/a", "/d"].map(|path| LogEntry::builder().path(path).build());
+
+        assert_eq!(
+            top_paths(&entries, 3),
+            vec![("/a".to_string(), 3), ("/b".to_string(), 2), ("/c".to_string(), 1)]
+        );
+        assert_eq!(top_paths(&entries, 10).len(), 4);
+        assert!(top_paths(&entries, 0).is_empty());
+        assert!(top_paths(&[], 3).is_empty());
+    }
+
+    #[test]
+    fn test_normalize_path() {
+        let rules = PathRule::defaults();
+        let cases = [
+            ("/users/42", "/users/{id}"),
+            ("/users/42/orders/7/", "/users/{id}/orders/{id}/"),
+            ("/items/3f2504e0-4f89-11d3-9a0c-0305e82c3301", "/items/{uuid}"),
+            ("/items/3F2504E0-4F89-11D3-9A0C-0305E82C3301/edit", "/items/{uuid}/edit"),
+            ("/blobs/9b74c9897bac770ffc029102a200c5de", "/blobs/{hash}"),
+            ("/api/v2/users", "/api/v2/users"),
+            ("/users/42abc", "/users/42abc"),
+            ("/static/deadbeef", "/static/deadbeef"),
+            ("/", "/"),
+            ("", ""),
+        ];
+
+        for (path, normalized) in cases.iter() {
+            assert_eq!(normalize_path(path, &rules), *normalized, "Wrong result for path: {path}");
+        }
+        assert_eq!(normalize_path("/users/42", &[]), "/users/42");
+
+        // Custom rules come first, so they override the defaults.
+        let mut rules = vec![
+            PathRule::new("[a-z]+@[a-z.]+", "{email}").unwrap(),
+            PathRule::new("2024", "{year}").unwrap(),
+        ];
+        rules.extend(PathRule::defaults());
+        assert_eq!(normalize_path("/archive/2024/7/bob@example.com", &rules), "/archive/{year}/{id}/{email}");
+    }
+
+    #[test]
+    fn test_top_normalized_paths() {
+        let entries = ["/users/1", "/users/2", "/users/3/orders", "/health", "/users/4"]
+            .map(|path| LogEntry::builder().path(path).build());
+
+        assert_eq!(
+            top_normalized_paths(&entries, 2, &PathRule::defaults()),
+            [("/users/{id}".to_string(), 3), ("/health".to_string(), 1)]
+        );
+        // The entries keep their raw paths.
+        assert_eq!(entries[0].path, "/users/1");
+    }
+
+    #[test]
+    fn test_approx_quantiles() {
+        // Uniform, heavy-tailed and mostly-empty responses.
+        let distributions: [Vec<u64>; 3] = [
+            (1..=100_000).collect(),
+            (1..=2_000u64).map(|i| i * i * i).collect(),
+            (0..1_000).map(|i| if i % 10 == 0 { 4096 + i } else { 0 }).collect(),
+        ];
+
+        for sizes in distributions {
+            let exact = SizeStats::from_sizes(sizes.iter().copied());
+            let mut approx = ApproxQuantiles::new();
+            for size in &sizes {
+                approx.add(*size);
+            }
+            assert_eq!(approx.count(), exact.count() as u64);
+            assert_eq!(approx.total(), exact.total());
+
+            for p in [0.0, 1.0, 25.0, 50.0, 90.0, 95.0, 99.0, 99.9, 100.0] {
+                let (expected, estimate) = (exact.percentile(p) as f64, approx.quantile(p / 100.0) as f64);
+                let tolerance = expected * RELATIVE_ACCURACY + 1.0;
+                assert!(
+                    (estimate - expected).abs() <= tolerance,
+                    "p{p}: estimated {estimate}, exact {expected}"
+                );
+            }
+        }
+
+        let entries = [10, 1000, 30].map(|size| LogEntry {
+            size,
+            ..Default::default()
+        });
+        let approx = entries.iter().collect::<ApproxQuantiles>();
+        assert_eq!(
+            approx.to_string(),
+            "total: 1.0 KiB\naverage: 347 B\np50: ~30 B\np95: ~1002 B\np99: ~1002 B\n"
+        );
+        assert_eq!(ApproxQuantiles::new().quantile(0.5), 0);
+        assert_eq!(ApproxQuantiles::new().mean(), 0.0);
+    }
+
+    #[test]
+    fn test_hits_by_prefix() {
+        let entries = ["/api/users/1", "/static/app.js", "/api/orders", "/api/users/2", "/", "/api"]
+            .map(|path| LogEntry::builder().path(path).build());
+
+        let prefixes = |depth| hits_by_prefix(&entries, depth);
+        assert_eq!(
+            prefixes(1),
+            [("/api".to_string(), 4), ("/".to_string(), 1), ("/static".to_string(), 1)]
+        );
+        assert_eq!(prefixes(2)[0], ("/api/users".to_string(), 2));
+        assert_eq!(prefixes(2).len(), 5);
+        assert_eq!(prefixes(0), [("/".to_string(), 6)]);
+    }
+
+    #[test]
+    fn test_hits_by_referrer_host() {
+        let entries = [
+            Some("https://example.com/a"),
+            Some("https://google.com/search?q=x"),
+            Some("https://example.com/b"),
+            None,
+            Some("garbage"),
+        ]
+        .into_iter()
+        .map(|referrer| LogEntry {
+            referrer: referrer.map(str::to_string),
+            ..Default::default()
+        })
+        .collect::<Vec<_>>();
+
+        assert_eq!(
+            hits_by_referrer_host(&entries),
+            [
+                (None, 2),
+                (Some("example.com".to_string()), 2),
+                (Some("google.com".to_string()), 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bytes_by_ip() {
+        let entries = [("10.0.0.1", 100), ("10.0.0.2", 50), ("10.0.0.1", u64::from(u32::MAX)), ("10.0.0.3", 50)]
+            .into_iter()
+            .map(|(ip, size)| LogEntry {
+                ip: ip.parse().unwrap(),
+                size,
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+
+        let totals = bytes_by_ip(&entries);
+        assert_eq!(totals.len(), 3);
+        assert_eq!(totals[&"10.0.0.1".parse::<IpAddr>().unwrap()], u64::from(u32::MAX) + 100);
+        assert_eq!(totals[&"10.0.0.2".parse::<IpAddr>().unwrap()], 50);
+
+        let top = top_ips(&entries, 2);
+        assert_eq!(top.len(), 2);
+        assert_eq!(top[0].0, "10.0.0.1".parse::<IpAddr>().unwrap());
+        assert_eq!(top[0].1, ClientTraffic { bytes: u64::from(u32::MAX) + 100, requests: 2 });
+        assert_eq!(top[1].0, "10.0.0.2".parse::<IpAddr>().unwrap());
+        assert_eq!(top[1].1, ClientTraffic { bytes: 50, requests: 1 });
+
+        let huge = [u64::MAX, 1].map(|size| LogEntry::builder().ip([10, 0, 0, 9]).size(size).build());
+        assert_eq!(bytes_by_ip(&huge)[&IpAddr::from([10, 0, 0, 9])], u64::MAX);
+        assert_eq!(top_ips(&huge, 1)[0].1, ClientTraffic { bytes: u64::MAX, requests: 2 });
+    }
+
+    #[test]
+    fn test_bucket_by_interval() {
+        let entries = [(10, 0, 5), (10, 0, 59), (10, 1, 0), (10, 3, 30), (10, 3, 31)]
+            .into_iter()
+            .map(|(h, m, s)| LogEntry {
+                timestamp: Utc.with_ymd_and_hms(2024, 1, 15, h, m, s).unwrap().fixed_offset(),
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+
+        let buckets = bucket_by_interval(&entries, TimeDelta::minutes(1));
+        let expected = [((10, 0), 2), ((10, 1), 1), ((10, 3), 2)]
+            .into_iter()
+            .map(|((h, m), count)| (Utc.with_ymd_and_hms(2024, 1, 15, h, m, 0).unwrap(), count))
+            .collect::<BTreeMap<_, _>>();
+        assert_eq!(buckets, expected);
+
+        let buckets = bucket_by_interval(&entries, TimeDelta::hours(1));
+        assert_eq!(buckets.len(), 1);
+        assert_eq!(buckets[&Utc.with_ymd_and_hms(2024, 1, 15, 10, 0, 0).unwrap()], 5);
+        assert!(bucket_by_interval(&[], TimeDelta::minutes(1)).is_empty());
+    }
+
+    #[test]
+    fn test_detect_rate_spikes() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 15, 10, 0, 0).unwrap();
+        let request = |ip: &str, millis: i64| LogEntry {
+            ip: ip.parse().unwrap(),
+            timestamp: (start + TimeDelta::milliseconds(millis)).fixed_offset(),
+            ..Default::default()
+        };
+
+        // A steady client at one request every 2s, and a burst of 150 in 15s with a second
+        // burst after a quiet minute.
+        let mut entries = (0..120).map(|i| request("10.0.0.1", i * 2_000)).collect::<Vec<_>>();
+        entries.extend((0..150).map(|i| request("10.0.0.2", 30_000 + i * 100)));
+        entries.extend((0..101).map(|i| request("10.0.0.2", 200_000 + i * 10)));
+        entries.sort_by_key(|entry| entry.timestamp);
+
+        let spikes = detect_rate_spikes(&entries, TimeDelta::seconds(60), 100);
+        let burst = "10.0.0.2".parse().unwrap();
+        assert_eq!(
+            spikes,
+            [
+                (burst, start + TimeDelta::milliseconds(30_000 + 100 * 100)),
+                (burst, start + TimeDelta::milliseconds(200_000 + 100 * 10)),
+            ]
+        );
+
+        // The steady client keeps 30 requests in every window, so it is flagged only once.
+        let spikes = detect_rate_spikes(&entries, TimeDelta::seconds(60), 29);
+        assert_eq!(spikes.len(), 3);
+        assert!(spikes.contains(&("10.0.0.1".parse().unwrap(), start + TimeDelta::seconds(58))));
+        assert_eq!(detect_rate_spikes(&entries, TimeDelta::seconds(60), 30).len(), 2);
+        assert!(detect_rate_spikes(&entries, TimeDelta::seconds(60), 1000).is_empty());
+    }
+
+    #[test]
+    fn test_sort_entries() {
+        let mut entries = [(500, 10), (200, 30), (404, 20), (200, 10)]
+            .map(|(status, size)| LogEntry::builder().status(status).size(size).build());
+
+        sort_entries(&mut entries, SortKey::Status, false);
+        let order = entries.iter().map(|entry| (entry.status, entry.size)).collect::<Vec<_>>();
+        assert_eq!(order, [(200, 30), (200, 10), (404, 20), (500, 10)]);
+
+        sort_entries(&mut entries, SortKey::Size, true);
+        let order = entries.iter().map(|entry| (entry.status, entry.size)).collect::<Vec<_>>();
+        assert_eq!(order, [(200, 30), (404, 20), (200, 10), (500, 10)]);
+
+        let mut entries = ["10.0.0.2", "10.0.0.10", "2001:db8::1", "10.0.0.1"]
+            .map(|ip| LogEntry::builder().ip(ip.parse::<IpAddr>().unwrap()).build());
+        sort_entries(&mut entries, SortKey::Ip, false);
+        let order = entries.iter().map(|entry| entry.ip.to_string()).collect::<Vec<_>>();
+        assert_eq!(order, ["10.0.0.1", "10.0.0.2", "10.0.0.10", "2001:db8::1"]);
+    }
+
+    #[test]
+    fn test_unique_ips_per_day() {
+        let entries = [
+            ("10.0.0.1", "2024-01-15T08:00:00+00:00"),
+            ("10.0.0.1", "2024-01-15T09:00:00+00:00"),
+            ("10.0.0.2", "2024-01-15T23:30:00+00:00"),
+            // 2024-01-16 01:00 local, still the 15th in UTC.
+            ("10.0.0.3", "2024-01-16T01:00:00+02:00"),
+            ("10.0.0.1", "2024-01-16T00:00:00+00:00"),
+        ]
+        .map(|(ip, timestamp)| {
+            LogEntry::builder()
+                .ip(ip.parse::<IpAddr>().unwrap())
+                .timestamp(DateTime::parse_from_rfc3339(timestamp).unwrap())
+                .build()
+        });
+
+        let days = unique_ips_per_day(&entries);
+        let day = |d| NaiveDate::from_ymd_opt(2024, 1, d).unwrap();
+        assert_eq!(days, BTreeMap::from([(day(15), 3), (day(16), 1)]));
+        assert!(unique_ips_per_day(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_slowest() {
+        let entries = [("/a", Some(30)), ("/b", None), ("/c", Some(250)), ("/d", Some(30)), ("/e", Some(5))]
+            .map(|(path, millis)| LogEntry {
+                path: path.to_string(),
+                duration: millis.map(std::time::Duration::from_millis),
+                ..Default::default()
+            });
+
+        let paths = slowest(&entries, 3).into_iter().map(|entry| entry.path.as_str()).collect::<Vec<_>>();
+        assert_eq!(paths, ["/c", "/a", "/d"]);
+        assert_eq!(slowest(&entries, 10).len(), 4);
+    }
+}
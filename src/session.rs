@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::parser::LogEntry;
+
+/// The idle gap after which a client's next request starts a new session.
+pub const DEFAULT_SESSION_GAP: TimeDelta = TimeDelta::minutes(30);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Session {
+    pub ip: IpAddr,
+    pub user_agent: Option<String>,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub requests: usize,
+    /// Paths in request order, including repeats.
+    pub paths: Vec<String>,
+}
+
+/// Groups requests from the same ip and user agent into sessions, starting a new one
+/// whenever more than `gap` passes between consecutive requests.
+///
+/// Each client's entries must be in timestamp order (a globally time-sorted log
+/// qualifies); out-of-order entries extend the current session without moving its end
+/// backwards. Sessions are returned in the order their first request appears.
+pub fn sessionize<'a>(entries: impl IntoIterator<Item = &'a LogEntry>, gap: TimeDelta) -> Vec<Session> {
+    let mut sessions: Vec<Session> = Vec::new();
+    let mut open: HashMap<(IpAddr, Option<&str>), usize> = HashMap::new();
+
+    for entry in entries {
+        let timestamp = entry.timestamp.to_utc();
+        let client = (entry.ip, entry.user_agent.as_deref());
+
+        if let Some(&index) = open.get(&client) {
+            let session = &mut sessions[index];
+            if timestamp - session.end <= gap {
+                session.end = session.end.max(timestamp);
+                session.requests += 1;
+                session.paths.push(entry.path.clone());
+                continue;
+            }
+        }
+
+        open.insert(client, sessions.len());
+        sessions.push(Session {
+            ip: entry.ip,
+            user_agent: entry.user_agent.clone(),
+            start: timestamp,
+            end: timestamp,
+            requests: 1,
+            paths: vec![entry.path.clone()],
+        });
+    }
+    sessions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::net::Ipv4Addr;
+
+    fn request(ip: u8, user_agent: &str, minute: i64, path: &str) -> LogEntry {
+        LogEntry::builder()
+            .ip(Ipv4Addr::new(10, 0, 0, ip))
+            .user_agent(user_agent)
+            .timestamp(Utc.with_ymd_and_hms(2024, 1, 15, 10, 0, 0).unwrap() + TimeDelta::minutes(minute))
+            .path(path)
+            .build()
+    }
+
+    #[test]
+    fn test_sessionize() {
+        let entries = [
+            request(1, "firefox", 0, "/"),
+            request(2, "curl", 1, "/api"),
+            request(1, "firefox", 20, "/about"),
+            request(1, "chrome", 21, "/"),
+            request(1, "firefox", 50, "/contact"),
+            request(1, "firefox", 90, "/"),
+        ];
+
+        let sessions = sessionize(&entries, DEFAULT_SESSION_GAP);
+        let summary = sessions
+            .iter()
+            .map(|s| (s.ip.to_string(), s.user_agent.clone().unwrap(), s.requests, s.paths.join(" ")))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            [
+                ("10.0.0.1".to_string(), "firefox".to_string(), 3, "/ /about /contact".to_string()),
+                ("10.0.0.2".to_string(), "curl".to_string(), 1, "/api".to_string()),
+                ("10.0.0.1".to_string(), "chrome".to_string(), 1, "/".to_string()),
+                ("10.0.0.1".to_string(), "firefox".to_string(), 1, "/".to_string()),
+            ]
+        );
+        assert_eq!(sessions[0].start, Utc.with_ymd_and_hms(2024, 1, 15, 10, 0, 0).unwrap());
+        assert_eq!(sessions[0].end, Utc.with_ymd_and_hms(2024, 1, 15, 10, 50, 0).unwrap());
+
+        let short = sessionize(&entries, TimeDelta::minutes(15));
+        let firefox = short
+            .iter()
+            .filter(|s| s.user_agent.as_deref() == Some("firefox"))
+            .map(|s| s.requests)
+            .collect::<Vec<_>>();
+        assert_eq!(firefox, [1, 1, 1, 1]);
+    }
+}
@@ -0,0 +1,193 @@
+use core::net::IpAddr;
+use core::time::Duration;
+
+use chrono::{DateTime, FixedOffset};
+
+use crate::parser::{
+    HTTP_METHODS, LogEntry, ParseError, ParseOptions, optional_field, parse_duration_value, parse_status,
+    split_absolute_target,
+};
+
+// 1705314252.123    152 10.0.0.5 TCP_MISS/200 1234 GET http://example.com/ - HIER_DIRECT/93.184.216.34 text/html
+const SQUID_FIELDS: usize = 10;
+
+/// Parses a line of Squid's native `access.log` format, `%ts.%03tu %6tr %>a %Ss/%03>Hs %<st
+/// %rm %ru %[un %Sh/%<a %mt`. The elapsed time is in milliseconds. The cache result code
+/// (`TCP_MISS`), the hierarchy code with its peer (`HIER_DIRECT/93.184.216.34`) and the
+/// content type have no `LogEntry` field of their own, so they go into `fields` as
+/// `cache_result`, `hierarchy` and `content_type`. Squid logs status `000` when no reply
+/// was sent, e.g. an aborted request; that parses as status 0.
+pub fn parse_squid(line: &str) -> Result<LogEntry, ParseError> {
+    parse_squid_with(line, &ParseOptions::default())
+}
+
+/// Like `parse_squid`, applying the method options and `keep_raw_timestamp`. The timestamp
+/// is always epoch seconds, so `timestamp_format` and `month_names` don't apply.
+pub fn parse_squid_with(line: &str, options: &ParseOptions) -> Result<LogEntry, ParseError> {
+    let values = line.split_whitespace().collect::<Vec<_>>();
+    if values.len() < SQUID_FIELDS {
+        return Err(ParseError::TooFewFields {
+            expected: SQUID_FIELDS,
+            found: values.len(),
+        });
+    }
+    if values.len() > SQUID_FIELDS {
+        return Err(ParseError::UnexpectedTrailing(values[SQUID_FIELDS..].join(" ")));
+    }
+    let [timestamp, elapsed, client, result, bytes, method, url, user, hierarchy, content_type] =
+        values.try_into().unwrap();
+
+    let mut method = method.to_string();
+    if options.uppercase_methods {
+        method.make_ascii_uppercase();
+    }
+    if options.strict_methods && !HTTP_METHODS.contains(&method.as_str()) {
+        return Err(ParseError::InvalidMethod(method));
+    }
+
+    let mut entry = LogEntry {
+        timestamp: parse_epoch_millis(timestamp)?,
+        duration: Some(Duration::from_millis(parse_duration_value(elapsed)?)),
+        ip: client.parse::<IpAddr>().map_err(|source| ParseError::InvalidIp {
+            value: client.to_string(),
+            source,
+        })?,
+        size: bytes.parse::<u64>().map_err(|source| ParseError::InvalidSize {
+            value: bytes.to_string(),
+            source,
+        })?,
+        user: optional_field(user),
+        raw_timestamp: options.keep_raw_timestamp.then(|| timestamp.to_string()),
+        ..LogEntry::default()
+    };
+
+    let (cache_result, status) = result.split_once('/').ok_or(ParseError::InvalidFormat)?;
+    entry.status = match status {
+        "000" => 0,
+        status => parse_status(status)?,
+    };
+    entry.fields.insert("cache_result".to_string(), cache_result.to_string());
+    if let Some(hierarchy) = optional_field(hierarchy) {
+        entry.fields.insert("hierarchy".to_string(), hierarchy);
+    }
+    if let Some(content_type) = optional_field(content_type) {
+        entry.fields.insert("content_type".to_string(), content_type);
+    }
+
+    // `CONNECT` logs `host:port` rather than a URL; keep it as the host.
+    if method == "CONNECT" && !url.contains('/') {
+        entry.method = method;
+        entry.host = Some(url.to_string());
+        return Ok(entry);
+    }
+    let (host, target) = split_absolute_target(url);
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path.to_string(), Some(query.to_string())),
+        None => (target.to_string(), None),
+    };
+    entry.method = method;
+    entry.host = host;
+    entry.path = path;
+    entry.query = query;
+
+    Ok(entry)
+}
+
+// "1705314252.123": seconds since the epoch with a millisecond fraction; always UTC.
+fn parse_epoch_millis(value: &str) -> Result<DateTime<FixedOffset>, ParseError> {
+    let invalid = || ParseError::InvalidTimestamp {
+        value: value.to_string(),
+        source: None,
+    };
+    let (seconds, millis) = value.split_once('.').unwrap_or((value, "0"));
+    if millis.len() > 3 || !millis.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let seconds = seconds.parse::<i64>().map_err(|_| invalid())?;
+    let nanos = format!("{millis:0<9}").parse::<u32>().map_err(|_| invalid())?;
+    let timestamp = DateTime::from_timestamp(seconds, nanos).ok_or_else(invalid)?;
+    Ok(timestamp.fixed_offset())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn test_parse_squid() {
+        let line = "1705314252.123    152 10.0.0.5 TCP_MISS/200 1234 GET http://example.com/api/users?id=5 - \
+                    HIER_DIRECT/93.184.216.34 application/json";
+        let entry = parse_squid(line).unwrap();
+        assert_eq!(
+            entry.timestamp,
+            Utc.with_ymd_and_hms(2024, 1, 15, 10, 24, 12).unwrap() + Duration::from_millis(123)
+        );
+        assert_eq!(entry.duration, Some(Duration::from_millis(152)));
+        assert_eq!(entry.ip.to_string(), "10.0.0.5");
+        assert_eq!(entry.status, 200);
+        assert_eq!(entry.size, 1234);
+        assert_eq!(entry.method, "GET");
+        assert_eq!(entry.host.as_deref(), Some("example.com"));
+        assert_eq!(entry.path, "/api/users");
+        assert_eq!(entry.query.as_deref(), Some("id=5"));
+        assert_eq!(entry.user, None);
+        assert_eq!(entry.fields["cache_result"], "TCP_MISS");
+        assert_eq!(entry.fields["hierarchy"], "HIER_DIRECT/93.184.216.34");
+        assert_eq!(entry.fields["content_type"], "application/json");
+    }
+
+    #[test]
+    fn test_parse_squid_connect_and_aborted() {
+        let line = "1705314253.001  60012 2001:db8::1 TCP_TUNNEL/200 5120 CONNECT example.com:443 frank \
+                    HIER_DIRECT/93.184.216.34 -";
+        let entry = parse_squid(line).unwrap();
+        assert_eq!(entry.host.as_deref(), Some("example.com:443"));
+        assert_eq!(entry.path, "");
+        assert_eq!(entry.user.as_deref(), Some("frank"));
+        assert!(!entry.fields.contains_key("content_type"));
+
+        let line = "1705314254.500      0 10.0.0.5 NONE_NONE/000 0 GET http://example.com/ - HIER_NONE/- -";
+        let entry = parse_squid(line).unwrap();
+        assert_eq!(entry.status, 0);
+        assert_eq!(entry.path, "/");
+    }
+
+    #[test]
+    fn test_parse_squid_errors() {
+        let line = "1705314252.123 152 10.0.0.5 TCP_MISS/200 1234 GET http://example.com/ -";
+        assert_eq!(parse_squid(line), Err(ParseError::TooFewFields { expected: 10, found: 8 }));
+
+        let line = "1705314252.1234 152 10.0.0.5 TCP_MISS/200 1234 GET http://example.com/ - HIER_NONE/- -";
+        assert!(matches!(parse_squid(line), Err(ParseError::InvalidTimestamp { source: None, .. })));
+
+        let line = "1705314252.123 152 10.0.0.5 TCP_MISS 1234 GET http://example.com/ - HIER_NONE/- -";
+        assert_eq!(parse_squid(line), Err(ParseError::InvalidFormat));
+
+        let line = "1705314252.123 152 10.0.0.5 TCP_MISS/999 1234 GET http://example.com/ - HIER_NONE/- -";
+        assert_eq!(parse_squid(line), Err(ParseError::InvalidStatus("999".to_string())));
+    }
+
+    #[test]
+    fn test_parse_squid_with_options() {
+        let line = "1705314252.123 152 10.0.0.5 TCP_MISS/200 1234 get http://example.com/ - HIER_DIRECT/93.184.216.34 -";
+        assert_eq!(parse_squid(line).unwrap().method, "get");
+
+        let options = ParseOptions {
+            uppercase_methods: true,
+            keep_raw_timestamp: true,
+            ..Default::default()
+        };
+        let entry = parse_squid_with(line, &options).unwrap();
+        assert_eq!(entry.method, "GET");
+        assert_eq!(entry.raw_timestamp.as_deref(), Some("1705314252.123"));
+
+        let strict = ParseOptions {
+            strict_methods: true,
+            ..Default::default()
+        };
+        assert_eq!(parse_squid_with(line, &strict), Err(ParseError::InvalidMethod("get".to_string())));
+        let line = "1705314253.001 60012 10.0.0.5 TCP_TUNNEL/200 5120 CONNECT example.com:443 - HIER_DIRECT/93.184.216.34 -";
+        assert_eq!(parse_squid_with(line, &strict).unwrap().method, "CONNECT");
+    }
+}
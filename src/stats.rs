@@ -0,0 +1,187 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::parser::{LogEntry, ParseError};
+
+/// Running totals of parse outcomes, keyed by `ParseError::kind_name` for failures.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParseStats {
+    pub parsed: usize,
+    pub failed: BTreeMap<&'static str, usize>,
+}
+
+impl ParseStats {
+    pub fn new() -> Self {
+        ParseStats::default()
+    }
+
+    pub fn record(&mut self, result: &Result<LogEntry, ParseError>) {
+        match result {
+            Ok(_) => self.record_parsed(),
+            Err(err) => self.record_error(err),
+        }
+    }
+
+    pub fn record_parsed(&mut self) {
+        self.parsed += 1;
+    }
+
+    pub fn record_error(&mut self, err: &ParseError) {
+        *self.failed.entry(err.kind_name()).or_default() += 1;
+    }
+
+    pub fn skipped(&self) -> usize {
+        self.failed.values().sum()
+    }
+
+    pub fn total(&self) -> usize {
+        self.parsed + self.skipped()
+    }
+}
+
+// 998112 -> "998,112"
+fn with_separators(n: usize) -> String {
+    let digits = n.to_string();
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+// parsed 998,112 / skipped 1,888 (invalid_timestamp: 1,200, invalid_size: 688)
+impl fmt::Display for ParseStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "parsed {} / skipped {}",
+            with_separators(self.parsed),
+            with_separators(self.skipped())
+        )?;
+        if self.failed.is_empty() {
+            return Ok(());
+        }
+
+        let mut kinds = self.failed.iter().collect::<Vec<_>>();
+        kinds.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        let kinds = kinds
+            .into_iter()
+            .map(|(kind, count)| format!("{}: {}", kind, with_separators(*count)))
+            .collect::<Vec<_>>();
+        write!(f, " ({})", kinds.join(", "))
+    }
+}
+
+/// Failures grouped by `ParseError::kind_name`, each with its count and the first few
+/// offending lines, to show what a log source gets wrong.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ErrorReport {
+    examples_per_kind: usize,
+    kinds: BTreeMap<&'static str, ErrorKindReport>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ErrorKindReport {
+    pub count: usize,
+    /// `(line number, raw line)` of the first failures of this kind.
+    pub examples: Vec<(usize, String)>,
+}
+
+impl ErrorReport {
+    pub fn new(examples_per_kind: usize) -> Self {
+        ErrorReport {
+            examples_per_kind,
+            kinds: BTreeMap::new(),
+        }
+    }
+
+    pub fn record(&mut self, err: &ParseError, line_number: usize, raw: &str) {
+        let kind = self.kinds.entry(err.kind_name()).or_default();
+        kind.count += 1;
+        if kind.examples.len() < self.examples_per_kind {
+            kind.examples.push((line_number, raw.to_string()));
+        }
+    }
+
+    /// Kinds by descending count, ties by name.
+    pub fn kinds(&self) -> Vec<(&'static str, &ErrorKindReport)> {
+        let mut kinds = self.kinds.iter().map(|(name, kind)| (*name, kind)).collect::<Vec<_>>();
+        kinds.sort_by(|a, b| b.1.count.cmp(&a.1.count).then_with(|| a.0.cmp(b.0)));
+        kinds
+    }
+}
+
+// invalid_timestamp: 1,200
+//   line 17: 10.0.0.5 - - [15/Foo/2024:10:24:12 +0000] "GET / HTTP/1.1" 200 0
+impl fmt::Display for ErrorReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, kind) in self.kinds() {
+            writeln!(f, "{}: {}", name, with_separators(kind.count))?;
+            for (line_number, raw) in &kind.examples {
+                writeln!(f, "  line {}: {}", line_number, raw)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invalid_size() -> ParseError {
+        ParseError::InvalidSize {
+            value: "12k".to_string(),
+            source: "12k".parse::<u64>().unwrap_err(),
+        }
+    }
+
+    #[test]
+    fn test_parse_stats_summary() {
+        let mut stats = ParseStats::new();
+        for _ in 0..998_112 {
+            stats.record_parsed();
+        }
+        for _ in 0..688 {
+            stats.record(&Err(invalid_size()));
+        }
+        for _ in 0..1200 {
+            stats.record(&Err(ParseError::InvalidTimestamp {
+                value: "x".to_string(),
+                source: None,
+            }));
+        }
+        stats.record(&Ok(LogEntry::default()));
+
+        assert_eq!(stats.parsed, 998_113);
+        assert_eq!(stats.skipped(), 1888);
+        assert_eq!(stats.total(), 1_000_001);
+        assert_eq!(
+            stats.to_string(),
+            "parsed 998,113 / skipped 1,888 (invalid_timestamp: 1,200, invalid_size: 688)"
+        );
+        assert_eq!(ParseStats::new().to_string(), "parsed 0 / skipped 0");
+    }
+
+    #[test]
+    fn test_error_report() {
+        let mut report = ErrorReport::new(2);
+        for line_number in 1..=5 {
+            report.record(&invalid_size(), line_number, &format!("size {line_number}"));
+        }
+        report.record(&ParseError::InvalidFormat, 9, "garbage");
+
+        let kinds = report.kinds();
+        assert_eq!(kinds.iter().map(|(name, _)| *name).collect::<Vec<_>>(), ["invalid_size", "invalid_format"]);
+        assert_eq!(kinds[0].1.count, 5);
+        assert_eq!(kinds[0].1.examples, [(1, "size 1".to_string()), (2, "size 2".to_string())]);
+        assert_eq!(
+            report.to_string(),
+            "invalid_size: 5\n  line 1: size 1\n  line 2: size 2\ninvalid_format: 1\n  line 9: garbage\n"
+        );
+        assert_eq!(ErrorReport::new(3).to_string(), "");
+    }
+}
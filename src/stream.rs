@@ -0,0 +1,16 @@
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+use tokio_stream::wrappers::LinesStream;
+use tokio_stream::{Stream, StreamExt};
+
+use crate::parser::{LogEntry, ParseError, parse_common_log};
+use crate::reader::is_blank_or_comment;
+
+/// Parses common log lines as they arrive; only the reading is async. Blank and `#`
+/// comment lines are skipped, as in `LogParser`.
+pub fn parse_stream<R: AsyncBufRead + Unpin>(reader: R) -> impl Stream<Item = Result<LogEntry, ParseError>> {
+    LinesStream::new(reader.lines()).filter_map(|line| match line {
+        Ok(line) if is_blank_or_comment(&line) => None,
+        Ok(line) => Some(parse_common_log(&line)),
+        Err(err) => Some(Err(ParseError::Io(err))),
+    })
+}
@@ -0,0 +1,168 @@
+use core::net::IpAddr;
+use core::time::Duration;
+
+use chrono::{NaiveDate, NaiveTime};
+
+use crate::parser::{LogEntry, ParseError, optional_field, parse_duration_value, parse_status};
+
+// #Fields: date time c-ip cs-method cs-uri-stem cs-uri-query sc-status sc-bytes
+#[derive(Debug, Clone, Default)]
+pub struct W3cParser {
+    fields: Option<Vec<String>>,
+}
+
+impl W3cParser {
+    pub fn new() -> Self {
+        W3cParser::default()
+    }
+
+    /// Returns `None` for directive lines, which only update the parser state.
+    pub fn parse_line(&mut self, line: &str) -> Option<Result<LogEntry, ParseError>> {
+        if let Some(directive) = line.strip_prefix('#') {
+            if let Some(fields) = directive.strip_prefix("Fields:") {
+                self.fields = Some(fields.split_whitespace().map(str::to_string).collect());
+            }
+            return None;
+        }
+
+        Some(self.parse_row(line))
+    }
+
+    fn parse_row(&self, line: &str) -> Result<LogEntry, ParseError> {
+        let fields = self.fields.as_ref().ok_or(ParseError::InvalidFormat)?;
+        let values = line.split_whitespace().collect::<Vec<_>>();
+        if values.len() < fields.len() {
+            return Err(ParseError::TooFewFields {
+                expected: fields.len(),
+                found: values.len(),
+            });
+        }
+        if values.len() > fields.len() {
+            return Err(ParseError::UnexpectedTrailing(values[fields.len()..].join(" ")));
+        }
+
+        let mut entry = LogEntry::default();
+        let mut date = None;
+        let mut time = None;
+        for (field, value) in fields.iter().zip(values) {
+            match field.as_str() {
+                "date" => date = Some(value),
+                "time" => time = Some(value),
+                "c-ip" => {
+                    entry.ip = value
+                        .parse::<IpAddr>()
+                        .map_err(|source| ParseError::InvalidIp {
+                            value: value.to_string(),
+                            source,
+                        })?;
+                }
+                "cs-username" => entry.user = optional_field(value),
+                "cs-method" => entry.method = value.to_string(),
+                "cs-uri-stem" => entry.path = value.to_string(),
+                "cs-uri-query" => entry.query = optional_field(value),
+                "cs-version" => entry.protocol = value.to_string(),
+                "sc-status" => entry.status = parse_status(value)?,
+                "sc-bytes" => {
+                    entry.size = match value {
+                        "-" => 0,
+                        value => value.parse::<u64>().map_err(|source| ParseError::InvalidSize {
+                            value: value.to_string(),
+                            source,
+                        })?,
+                    };
+                }
+                // IIS logs time-taken in milliseconds.
+                "time-taken" if value != "-" => {
+                    entry.duration = Some(Duration::from_millis(parse_duration_value(value)?));
+                }
+                // W3C encodes spaces inside header values as `+`.
+                "cs(Referer)" => entry.referrer = optional_field(value),
+                "cs(User-Agent)" => entry.user_agent = optional_field(value).map(|ua| ua.replace('+', " ")),
+                _ => {}
+            }
+        }
+
+        // W3C timestamps are always UTC.
+        if let (Some(date), Some(time)) = (date, time) {
+            let invalid = |source| ParseError::InvalidTimestamp {
+                value: format!("{date} {time}"),
+                source: Some(source),
+            };
+            let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(invalid)?;
+            let time = NaiveTime::parse_from_str(time, "%H:%M:%S").map_err(invalid)?;
+            entry.timestamp = date.and_time(time).and_utc().fixed_offset();
+        } else if let Some(value) = date.or(time) {
+            return Err(ParseError::InvalidTimestamp {
+                value: value.to_string(),
+                source: None,
+            });
+        }
+
+        Ok(entry)
+    }
+}
+
+pub fn parse_w3c<'a>(lines: impl IntoIterator<Item = &'a str>) -> Vec<Result<LogEntry, ParseError>> {
+    let mut parser = W3cParser::new();
+
+    lines
+        .into_iter()
+        .filter_map(|line| parser.parse_line(line))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    const IIS_LOG: &str = "\
+#Software: Microsoft Internet Information Services 10.0
+#Version: 1.0
+#Date: 2024-01-15 10:24:00
+#Fields: date time s-ip cs-method cs-uri-stem cs-uri-query s-port cs-username c-ip cs(User-Agent) cs(Referer) sc-status sc-substatus sc-win32-status sc-bytes time-taken
+2024-01-15 10:24:12 10.0.0.1 GET /api/users id=5 443 - 10.0.0.5 Mozilla/5.0+(Windows+NT+10.0) - 200 0 0 1234 15
+2024-01-15 10:24:13 10.0.0.1 POST /api/login - 443 frank 2001:db8::1 curl/8.0 https://example.com/ 401 1 0 89 3";
+
+    #[test]
+    fn test_parse_iis_log() {
+        let entries = parse_w3c(IIS_LOG.lines());
+        assert_eq!(entries.len(), 2);
+
+        let first = entries[0].as_ref().unwrap();
+        assert_eq!(first.ip.to_string(), "10.0.0.5");
+        assert_eq!(first.timestamp, Utc.with_ymd_and_hms(2024, 1, 15, 10, 24, 12).unwrap());
+        assert_eq!(first.method, "GET");
+        assert_eq!(first.path, "/api/users");
+        assert_eq!(first.query, Some("id=5".to_string()));
+        assert_eq!(first.status, 200);
+        assert_eq!(first.size, 1234);
+        assert_eq!(first.user_agent, Some("Mozilla/5.0 (Windows NT 10.0)".to_string()));
+        assert_eq!(first.referrer, None);
+        assert_eq!(first.duration, Some(Duration::from_millis(15)));
+
+        let second = entries[1].as_ref().unwrap();
+        assert_eq!(second.ip.to_string(), "2001:db8::1");
+        assert_eq!(second.user, Some("frank".to_string()));
+        assert_eq!(second.query, None);
+        assert_eq!(second.referrer, Some("https://example.com/".to_string()));
+        assert_eq!(second.status, 401);
+    }
+
+    #[test]
+    fn test_rows_without_fields_directive() {
+        let mut parser = W3cParser::new();
+        assert!(parser.parse_line("#Version: 1.0").is_none());
+
+        let row = parser.parse_line("2024-01-15 10:24:12 10.0.0.5 GET /api 200 1234");
+        assert!(matches!(row, Some(Err(ParseError::InvalidFormat))));
+
+        parser.parse_line("#Fields: date time c-ip cs-method cs-uri-stem sc-status sc-bytes");
+        let row = parser.parse_line("2024-01-15 10:24:12 10.0.0.5 GET /api 200");
+        assert_eq!(row, Some(Err(ParseError::TooFewFields { expected: 7, found: 6 })));
+        let row = parser.parse_line("2024-01-15 10:24:12 10.0.0.5 GET /api 200 1234 extra");
+        assert_eq!(row, Some(Err(ParseError::UnexpectedTrailing("extra".to_string()))));
+        let row = parser.parse_line("2024-01-15 10:24:12 10.0.0.5 GET /api 200 1234");
+        assert!(matches!(row, Some(Ok(_))));
+    }
+}
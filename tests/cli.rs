@@ -0,0 +1,42 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+const VALID: &str = "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"GET /api HTTP/1.1\" 200 567\n";
+
+// Runs the binary with `input` fed through stdin.
+fn run(args: &[&str], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_common-log-parser"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn validate_exit_codes() {
+    assert_eq!(run(&["--validate"], VALID).status.code(), Some(0));
+    assert_eq!(run(&["--validate"], &format!("{VALID}not a log line\n")).status.code(), Some(2));
+    assert_eq!(run(&["--validate", "tests/fixtures/missing.log"], "").status.code(), Some(1));
+}
+
+#[test]
+fn prints_parse_stats_without_skip_invalid() {
+    let output = run(&["--count"], &VALID.repeat(2));
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "parsed 2 / skipped 0\n");
+}
+
+#[test]
+fn fields_with_debug_output_is_a_usage_error() {
+    let output = run(&["--fields", "ip,status"], VALID);
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("--fields needs `--format csv` or a json format"));
+    assert!(stderr.contains("Usage:"));
+
+    assert!(run(&["--fields", "ip,status", "--format", "csv"], VALID).status.success());
+}
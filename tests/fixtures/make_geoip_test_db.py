@@ -0,0 +1,106 @@
+#!/usr/bin/env python3
+"""Writes geoip-country-test.mmdb, a tiny IPv4 country database for the geoip tests.
+
+The networks mirror a few entries of MaxMind's GeoIP2-Country-Test.mmdb. Run from this
+directory: python3 make_geoip_test_db.py
+"""
+
+import ipaddress
+import struct
+
+NETWORKS = {
+    "81.2.69.160/27": "GB",
+    "89.160.20.112/28": "SE",
+    "216.160.83.56/29": "US",
+}
+
+
+def control(type_, size):
+    # Types above 7 are "extended": a zero type in the control byte, then type - 7. Sizes
+    # from 29 store the remainder in a following byte.
+    size_bits, extra = (size, b"") if size < 29 else (29, bytes([size - 29]))
+    if type_ <= 7:
+        return bytes([type_ << 5 | size_bits]) + extra
+    return bytes([size_bits, type_ - 7]) + extra
+
+
+def encode(value):
+    if isinstance(value, str):
+        data = value.encode()
+        return control(2, len(data)) + data
+    if isinstance(value, dict):
+        out = control(7, len(value))
+        for key, item in value.items():
+            out += encode(key) + encode(item)
+        return out
+    if isinstance(value, list):
+        return control(11, len(value)) + b"".join(encode(item) for item in value)
+    if isinstance(value, tuple):
+        type_, number = value
+        data = number.to_bytes((number.bit_length() + 7) // 8, "big")
+        return control(type_, len(data)) + data
+    raise TypeError(value)
+
+
+def uint16(number):
+    return (5, number)
+
+
+def uint32(number):
+    return (6, number)
+
+
+def uint64(number):
+    return (9, number)
+
+
+def main():
+    data = b""
+    tree = [[None, None]]
+    for network, country in NETWORKS.items():
+        network = ipaddress.ip_network(network)
+        offset = len(data)
+        data += encode({"country": {"iso_code": country}})
+        bits = int(network.network_address)
+        node = 0
+        for depth in range(network.prefixlen):
+            bit = bits >> (31 - depth) & 1
+            if depth == network.prefixlen - 1:
+                tree[node][bit] = ("data", offset)
+            else:
+                if tree[node][bit] is None:
+                    tree.append([None, None])
+                    tree[node][bit] = ("node", len(tree) - 1)
+                node = tree[node][bit][1]
+
+    node_count = len(tree)
+
+    def record(value):
+        if value is None:
+            return node_count
+        kind, index = value
+        return index if kind == "node" else node_count + 16 + index
+
+    search_tree = b""
+    for left, right in tree:
+        search_tree += struct.pack(">I", record(left))[1:] + struct.pack(">I", record(right))[1:]
+
+    metadata = encode(
+        {
+            "binary_format_major_version": uint16(2),
+            "binary_format_minor_version": uint16(0),
+            "build_epoch": uint64(1_700_000_000),
+            "database_type": "GeoIP2-Country",
+            "description": {"en": "common-log-parser test database"},
+            "ip_version": uint16(4),
+            "languages": ["en"],
+            "node_count": uint32(node_count),
+            "record_size": uint16(24),
+        }
+    )
+    with open("geoip-country-test.mmdb", "wb") as out:
+        out.write(search_tree + bytes(16) + data + b"\xab\xcd\xefMaxMind.com" + metadata)
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,72 @@
+use std::fs;
+use std::io::{self, BufRead};
+
+use common_log_parser::{LogEntry, LogFormat, LogParser, decompress, open_maybe_compressed, parse_common_log};
+
+#[test]
+fn parses_fixture_file() {
+    let contents = fs::read_to_string("test.log").unwrap();
+
+    let entries = contents
+        .lines()
+        .map(parse_common_log)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    assert_eq!(entries.len(), 3);
+    assert_eq!(entries[0].method, "POST");
+    assert_eq!(entries[2].path, "/users");
+}
+
+fn parse_all(path: &str) -> Vec<LogEntry> {
+    open_maybe_compressed(path)
+        .unwrap()
+        .lines()
+        .map(|line| parse_common_log(&line.unwrap()).unwrap())
+        .collect()
+}
+
+#[test]
+fn parses_gzipped_fixture_like_plaintext() {
+    let plain = parse_all("test.log");
+    let gzipped = parse_all("tests/fixtures/test.log.gz");
+
+    assert_eq!(plain.len(), 3);
+    assert_eq!(plain, gzipped);
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+fn parses_zstd_fixture_like_plaintext() {
+    assert_eq!(parse_all("tests/fixtures/test.log.zst"), parse_all("test.log"));
+}
+
+#[cfg(feature = "bzip2")]
+#[test]
+fn parses_bzip2_fixture_like_plaintext() {
+    assert_eq!(parse_all("tests/fixtures/test.log.bz2"), parse_all("test.log"));
+}
+
+#[test]
+fn detects_compression_from_magic_bytes() {
+    let gzipped = fs::read("tests/fixtures/test.log.gz").unwrap();
+    let reader = decompress(io::Cursor::new(gzipped), None).unwrap();
+    assert_eq!(reader.lines().count(), 3);
+}
+
+#[test]
+fn parses_squid_fixture() {
+    let input = open_maybe_compressed("tests/fixtures/squid_access.log").unwrap();
+    let entries = LogParser::with_format(input, &LogFormat::Squid)
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    assert_eq!(entries.len(), 7);
+    assert_eq!(entries[2].method, "CONNECT");
+    assert_eq!(entries[2].host.as_deref(), Some("www.google.com:443"));
+    assert_eq!(entries[4].status, 403);
+    assert_eq!(entries[4].user.as_deref(), Some("alice"));
+    assert_eq!(entries[6].fields["cache_result"], "TCP_MISS_ABORTED");
+    assert_eq!(entries[6].status, 0);
+}
@@ -0,0 +1,30 @@
+use tokio::io::{AsyncWriteExt, BufReader};
+use tokio_stream::StreamExt;
+
+use common_log_parser::ParseError;
+use common_log_parser::stream::parse_stream;
+
+#[tokio::test]
+async fn parses_lines_from_duplex_pipe() {
+    let (mut client, server) = tokio::io::duplex(64);
+
+    let writer = tokio::spawn(async move {
+        let lines = [
+            "10.0.0.5 - - [15/Jan/2024:10:24:12 +0000] \"POST /api/login HTTP/1.1\" 201 567\n",
+            "\n",
+            "not a log line\n",
+            "8.8.8.8 - - [15/Jan/2024:10:29:47 +0000] \"DELETE /users HTTP/1.1\" 403 89\n",
+        ];
+        for line in lines {
+            client.write_all(line.as_bytes()).await.unwrap();
+        }
+    });
+
+    let results = parse_stream(BufReader::new(server)).collect::<Vec<_>>().await;
+    writer.await.unwrap();
+
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0].as_ref().unwrap().path, "/api/login");
+    assert_eq!(results[1].as_ref().unwrap_err(), &ParseError::InvalidFormat);
+    assert_eq!(results[2].as_ref().unwrap().status, 403);
+}